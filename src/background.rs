@@ -0,0 +1,272 @@
+//! Provides a trait for what a ray sees when it
+//! doesn't hit anything in the world, which also
+//! lights the scene from infinitely far away.
+
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::codecs::hdr::HdrDecoder;
+use image::error::{DecodingError, ImageFormatHint};
+use image::{ImageError, ImageResult, RgbImage};
+
+use super::distribution::Distribution2D;
+use super::vec::{Color, Vec3};
+
+pub trait Background: Send + Sync {
+    /// The light arriving from infinitely far away, going
+    /// against `direction`.
+    fn color(&self, direction: Vec3) -> Color;
+
+    /// Picks a direction to gather the background's light from,
+    /// favoring the brighter parts. Returns none for backgrounds
+    /// that are smooth enough that they aren't worth sampling.
+    fn random(&self) -> Option<Vec3> {
+        None
+    }
+
+    /// The probability density, over solid angle, with
+    /// which `random` picks `direction`.
+    fn pdf_value(&self, _direction: Vec3) -> f64 {
+        0.0
+    }
+}
+
+/// The same color in every direction. Black makes for
+/// scenes lit only by their emissive materials.
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Background for SolidColor {
+    fn color(&self, _direction: Vec3) -> Color {
+        self.color
+    }
+}
+
+/// A vertical blend between two colors, like a sky.
+pub struct Gradient {
+    bottom: Color,
+    top: Color,
+}
+
+impl Gradient {
+    pub fn new(bottom: Color, top: Color) -> Self {
+        Self { bottom, top }
+    }
+
+    /// A white to light blue sky.
+    pub fn sky() -> Self {
+        Self::new(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Background for Gradient {
+    fn color(&self, direction: Vec3) -> Color {
+        let unit_direction = direction.normalized();
+        let t = 0.5 * (unit_direction.y() + 1.0);
+        (1.0 - t) * self.bottom + t * self.top
+    }
+}
+
+/// An image wrapped around the whole scene, using the equirectangular
+/// (latitude/longitude) projection common for environment maps.
+///
+/// High dynamic range maps can have small, very bright spots, like the
+/// sun or studio lights, so directions get sampled in proportion to
+/// the brightness of the map.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    /// Linear colors, row by row from the top.
+    pixels: Vec<Color>,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    /// Creates a map from linear colors, given row by row from the top.
+    ///
+    /// # Panics
+    /// If the number of pixels doesn't match the dimensions.
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+
+        // each pixel's light gets blended into its neighbors' directions,
+        // so take the brightest of them to never miss a lit direction
+        let brightness = |x: usize, y: usize| luminance(pixels[y * width + x]);
+        let mut func = Vec::with_capacity(pixels.len());
+        for y in 0..height {
+            // rows near the poles get squeezed into a smaller solid angle,
+            // so they're less likely to be hit than their brightness suggests
+            let theta = PI * (y as f64 + 0.5) / height as f64;
+
+            for x in 0..width {
+                let mut brightest: f64 = 0.0;
+                for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                    for nx in [x + width - 1, x, x + 1] {
+                        brightest = brightest.max(brightness(nx % width, ny));
+                    }
+                }
+                func.push(brightest * theta.sin());
+            }
+        }
+        let distribution = Distribution2D::new(&func, width, height);
+
+        Self {
+            width,
+            height,
+            pixels,
+            distribution,
+        }
+    }
+
+    /// Loads an image, picking the decoder by the file's extension. Radiance
+    /// (`.hdr`) and OpenEXR (`.exr`) images are read as linear high dynamic
+    /// range colors, while anything else is read as an 8-bit image.
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("hdr") => Self::load_hdr(path),
+            Some("exr") => Self::load_exr(path),
+            _ => Ok(Self::from_image(&image::open(path)?.to_rgb8())),
+        }
+    }
+
+    fn load_hdr(path: &Path) -> ImageResult<Self> {
+        let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()?
+            .into_iter()
+            .map(|p| Color::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .collect();
+
+        Ok(Self::new(
+            metadata.width as usize,
+            metadata.height as usize,
+            pixels,
+        ))
+    }
+
+    fn load_exr(path: &Path) -> ImageResult<Self> {
+        use exr::prelude::*;
+
+        let image = read_first_rgba_layer_from_file(
+            path,
+            |resolution, _| {
+                let (width, height) = (resolution.width(), resolution.height());
+                (
+                    width,
+                    height,
+                    vec![Color::new(0.0, 0.0, 0.0); width * height],
+                )
+            },
+            |(width, _, pixels), position, (r, g, b, _): (f32, f32, f32, f32)| {
+                pixels[position.y() * *width + position.x()] =
+                    Color::new(r as f64, g as f64, b as f64);
+            },
+        )
+        .map_err(|e| {
+            ImageError::Decoding(DecodingError::new(
+                ImageFormatHint::Name(String::from("OpenEXR")),
+                e,
+            ))
+        })?;
+
+        let (width, height, pixels) = image.layer_data.channel_data.pixels;
+        Ok(Self::new(width, height, pixels))
+    }
+
+    pub fn from_image(image: &RgbImage) -> Self {
+        // undo the same gamma 2 that `Vec3::to_rgb` applies
+        let pixels = image
+            .pixels()
+            .map(|p| {
+                let c = |v: u8| (v as f64 / 255.0).powi(2);
+                Color::new(c(p[0]), c(p[1]), c(p[2]))
+            })
+            .collect();
+
+        Self::new(image.width() as usize, image.height() as usize, pixels)
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Maps a direction to texture coordinates, the same way `Sphere` does,
+    /// with `u` going around the Y axis and `v` running from -Y to +Y.
+    fn uv(direction: Vec3) -> (f64, f64) {
+        let d = direction.normalized();
+        let theta = (-d.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-d.z()).atan2(d.x()) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// The inverse of [`EnvironmentMap::uv`].
+    fn direction(u: f64, v: f64) -> Vec3 {
+        // measured from +Y this time, so it matches the image's rows
+        let theta = (1.0 - v) * PI;
+        let phi = u * 2.0 * PI;
+
+        Vec3::new(
+            -phi.cos() * theta.sin(),
+            theta.cos(),
+            phi.sin() * theta.sin(),
+        )
+    }
+}
+
+fn luminance(c: Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+impl Background for EnvironmentMap {
+    fn color(&self, direction: Vec3) -> Color {
+        let (u, v) = Self::uv(direction);
+
+        // blend the four nearest pixels, wrapping around horizontally
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let wrap_x = |x: f64| (x as isize).rem_euclid(self.width as isize) as usize;
+        let clamp_y = |y: f64| (y.max(0.0) as usize).min(self.height - 1);
+        let (x0, x1) = (wrap_x(x0), wrap_x(x0 + 1.0));
+        let (y0, y1) = (clamp_y(y0), clamp_y(y0 + 1.0));
+
+        let top = (1.0 - tx) * self.pixel(x0, y0) + tx * self.pixel(x1, y0);
+        let bottom = (1.0 - tx) * self.pixel(x0, y1) + tx * self.pixel(x1, y1);
+        (1.0 - ty) * top + ty * bottom
+    }
+
+    fn random(&self) -> Option<Vec3> {
+        let ((x, y), _) = self.distribution.sample();
+        Some(Self::direction(x, 1.0 - y))
+    }
+
+    fn pdf_value(&self, direction: Vec3) -> f64 {
+        let (u, v) = Self::uv(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        // convert from a density over the image to one over solid angle
+        self.distribution.pdf(u, 1.0 - v) / (2.0 * PI * PI * sin_theta)
+    }
+}
@@ -2,54 +2,51 @@ extern crate image;
 extern crate rand;
 extern crate raytracing;
 
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::Arc;
 
-use image::{ImageBuffer, ImageFormat, RgbImage};
+use clap::{Parser, ValueEnum};
+use image::ImageFormat;
 use rand::Rng;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
+use raytracing::background::{Background, Gradient, SolidColor};
+use raytracing::bvh::{BvhBuilder, SplitMethod};
 use raytracing::camera::Camera;
-use raytracing::hit::{Hit, World};
-use raytracing::material::{Dielectric, Lambertian, Metal, Scatter};
-use raytracing::ray::Ray;
+use raytracing::cuboid::Cuboid;
+use raytracing::description::{self, CameraDescription, RenderSettings, SceneDescription};
+use raytracing::hit::World;
+use raytracing::import::{gltf, pbrt, ImportedScene};
+use raytracing::instance::Instance;
+use raytracing::integrator::PathIntegrator;
+use raytracing::material::{Dielectric, DiffuseLight, Lambertian, Metal, Scatter};
+use raytracing::plane::Plane;
+use raytracing::quad::Quad;
+use raytracing::random;
+use raytracing::scene::Scene;
 use raytracing::sphere::Sphere;
-use raytracing::vec::{Color, Point3};
-
-fn ray_color(r: &Ray, world: &World, depth: u64) -> Color {
-    if depth == 0 {
-        // if we've exceeded the allowed number of ray bounces, stop gathering more info
-        return Color::new(0.0, 0.0, 0.0);
-    }
-    if let Some(rec) = world.hit(r, 0.001, f64::INFINITY) {
-        // let target = rec.p + rec.normal + Vec3::random_in_unit_sphere();
-        // let target = rec.p + rec.normal + Vec3::random_in_unit_sphere().normalized();
-        if let Some((attenuation, scattered)) = rec.mat.scatter(r, &rec) {
-            attenuation * ray_color(&scattered, world, depth - 1)
-        } else {
-            Color::new(0.0, 0.0, 0.0)
-        }
-    } else {
-        let unit_direction = r.direction().normalized();
-        let t = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
-    }
-}
+use raytracing::transform::Transform;
+use raytracing::vec::{Color, Point3, Vec3};
 
 fn random_scene() -> World {
     let mut world = World::new();
 
     let ground_material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    let ground = Box::new(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    ));
+    let ground = Box::new(
+        Plane::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            ground_material,
+        )
+        .unwrap(),
+    );
 
     world.push(ground);
 
     let glass_mat = Arc::new(Dielectric::new(1.5));
 
-    let mut rng = rand::thread_rng();
+    let mut rng = random::thread_rng();
     for a in -11..11 {
         for b in -11..11 {
             let center = Point3::new(
@@ -99,67 +96,380 @@ fn random_scene() -> World {
     world
 }
 
-fn main() {
-    // image
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-    const IMAGE_WIDTH: u64 = 1920;
-    const IMAGE_HEIGHT: u64 = ((IMAGE_WIDTH as f64) / ASPECT_RATIO) as u64;
-    const SAMPLES_PER_PIXEL: u64 = 50;
-    const MAX_DEPTH: u64 = 10;
+/// A box standing on the ground at `base`, turned
+/// `angle` degrees around its vertical axis.
+fn rotated_box(base: Point3, size: Vec3, angle: f64, mat: Arc<dyn Scatter>) -> Instance {
+    let corner = Point3::new(size.x() / 2.0, size.y(), size.z() / 2.0);
+    let cuboid = Cuboid::new(corner - size, corner, mat).unwrap();
 
-    // world
-    let world = random_scene();
-
-    // camera
-    let origin = Point3::new(13.0, 2.0, 3.0);
-    let look_at = Point3::new(0.0, 0.0, 0.0);
-    let roll = 0.0;
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
-
-    let cam = Camera::new(
-        origin,
-        look_at,
-        roll,
-        20.0,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
+    let transform = Transform::translate(base) * Transform::rotate(angle, Vec3::new(0.0, 1.0, 0.0));
+    Instance::new(Arc::new(cuboid), transform)
+}
+
+/// The Cornell box, lit by a single light in its ceiling. Returns the
+/// world, along with the light's shape, for sampling it directly.
+fn cornell_box() -> (World, World) {
+    let red: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light: Arc<dyn Scatter> = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+
+    let light = Arc::new(
+        Quad::new(
+            Point3::new(343.0, 554.0, 332.0),
+            Vec3::new(-130.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -105.0),
+            light,
+        )
+        .unwrap(),
     );
 
-    let mut image_buffer: RgbImage = ImageBuffer::new(IMAGE_WIDTH as u32, IMAGE_HEIGHT as u32);
-
-    for j in (0..IMAGE_HEIGHT).rev() {
-        eprintln!("Scanlines remaining: {j}");
-        let scanline: Vec<Color> = (0..IMAGE_WIDTH)
-            .into_par_iter()
-            .map(|i| {
-                let mut rng = rand::thread_rng();
-                (0..SAMPLES_PER_PIXEL)
-                    .into_iter()
-                    .map(|_| {
-                        let random_u: f64 = rng.gen();
-                        let random_v: f64 = rng.gen();
-
-                        let u = ((i as f64) + random_u) / ((IMAGE_WIDTH - 1) as f64);
-                        let v = ((j as f64) + random_v) / ((IMAGE_HEIGHT - 1) as f64);
-
-                        let r = cam.get_ray(u, v);
-                        ray_color(&r, &world, MAX_DEPTH)
-                    })
-                    .sum()
-            })
-            .collect();
-
-        for (i, pixel) in scanline.into_iter().enumerate() {
-            let pixel = pixel.to_rgb(SAMPLES_PER_PIXEL);
-            image_buffer.put_pixel(i as u32, (IMAGE_HEIGHT - j - 1) as u32, pixel);
+    let mut world = World::new();
+    let (o, x, y, z) = (
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(555.0, 0.0, 0.0),
+        Vec3::new(0.0, 555.0, 0.0),
+        Vec3::new(0.0, 0.0, 555.0),
+    );
+    world.push(Box::new(Quad::new(o + x, y, z, green).unwrap()));
+    world.push(Box::new(Quad::new(o, y, z, red).unwrap()));
+    world.push(Box::new(Quad::new(o, x, z, white.clone()).unwrap()));
+    world.push(Box::new(Quad::new(o + y, x, z, white.clone()).unwrap()));
+    world.push(Box::new(Quad::new(o + z, x, y, white.clone()).unwrap()));
+    world.push(Box::new(light.clone()));
+
+    world.push(Box::new(rotated_box(
+        Point3::new(347.5, 0.0, 377.5),
+        Vec3::new(165.0, 330.0, 165.0),
+        15.0,
+        white,
+    )));
+    world.push(Box::new(Sphere::new(
+        Point3::new(190.0, 90.0, 190.0),
+        90.0,
+        Arc::new(Dielectric::new(1.5)),
+    )));
+
+    (world, vec![Box::new(light)])
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SceneName {
+    /// Lots of small random spheres around three big ones, under the sky.
+    Random,
+    /// The Cornell box, with a box and a glass sphere inside.
+    Cornell,
+}
+
+impl SceneName {
+    /// The scene's camera and render settings, unless overridden.
+    fn defaults(self) -> (CameraDescription, RenderSettings) {
+        match self {
+            SceneName::Random => {
+                let mut camera =
+                    CameraDescription::new(Point3::new(13.0, 2.0, 3.0), Point3::new(0.0, 0.0, 0.0));
+                camera.fov = 20.0;
+                camera.aperture = 0.1;
+                camera.focus_dist = Some(10.0);
+
+                let render = RenderSettings {
+                    width: 1920,
+                    height: 1080,
+                    ..RenderSettings::default()
+                };
+                (camera, render)
+            }
+            SceneName::Cornell => {
+                let camera = CameraDescription::new(
+                    Point3::new(278.0, 278.0, -800.0),
+                    Point3::new(278.0, 278.0, 0.0),
+                );
+
+                let render = RenderSettings {
+                    width: 800,
+                    height: 800,
+                    ..RenderSettings::default()
+                };
+                (camera, render)
+            }
+        }
+    }
+
+    fn build(self, camera: Camera) -> Scene {
+        let (world, lights, background): (World, World, Box<dyn Background>) = match self {
+            SceneName::Random => (random_scene(), Vec::new(), Box::new(Gradient::sky())),
+            SceneName::Cornell => {
+                let (world, lights) = cornell_box();
+                let black = Color::new(0.0, 0.0, 0.0);
+                (world, lights, Box::new(SolidColor::new(black)))
+            }
+        };
+
+        let world = BvhBuilder::new()
+            .split_method(SplitMethod::Sah { bins: 16 })
+            .max_leaf_size(4)
+            .build(world);
+        eprintln!("BVH: {}", world.stats());
+
+        Scene::new(Box::new(world), camera)
+            .with_background(background)
+            .with_lights(lights)
+    }
+}
+
+/// A scene read from a file.
+enum SceneFile {
+    Description(SceneDescription),
+    Imported(ImportedScene),
+}
+
+impl SceneFile {
+    /// Reads a scene, in the format its extension names.
+    fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("pbrt") => Ok(SceneFile::Imported(pbrt::load(path)?)),
+            Some("gltf" | "glb") => Ok(SceneFile::Imported(gltf::load(path)?)),
+            _ => Ok(SceneFile::Description(description::parse::load(path)?)),
         }
     }
 
-    image_buffer
-        .save_with_format("image.png", ImageFormat::Png)
-        .unwrap();
+    fn settings(&self) -> (CameraDescription, RenderSettings) {
+        match self {
+            SceneFile::Description(d) => (d.camera.clone(), d.render.clone()),
+            SceneFile::Imported(i) => (i.camera.clone(), i.render.clone()),
+        }
+    }
+
+    /// Builds the scene, with the camera and render settings replaced.
+    fn build(
+        self,
+        camera: CameraDescription,
+        render: RenderSettings,
+    ) -> Result<Scene, Box<dyn Error>> {
+        match self {
+            SceneFile::Description(mut d) => {
+                d.camera = camera;
+                d.render = render;
+                Ok(d.build()?)
+            }
+            SceneFile::Imported(mut i) => {
+                i.camera = camera;
+                i.render = render;
+                Ok(i.build())
+            }
+        }
+    }
+}
+
+/// Renders a scene with a path tracer, and saves it as an image.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// The built-in scene to render.
+    #[arg(long, value_enum, default_value_t = SceneName::Random)]
+    scene: SceneName,
+
+    /// Scene file to render, instead of a built-in scene: a scene
+    /// description, a pbrt-v3 scene if it ends in `.pbrt`, or a glTF
+    /// scene if it ends in `.gltf` or `.glb`. Any other options
+    /// override the settings in the file.
+    #[arg(short, long, conflicts_with = "scene")]
+    file: Option<PathBuf>,
+
+    /// Width of the image, in pixels [default: depends on the scene]
+    #[arg(long, value_parser = parse_size)]
+    width: Option<usize>,
+
+    /// Height of the image, in pixels [default: the width over the aspect ratio]
+    #[arg(long, conflicts_with = "aspect_ratio", value_parser = parse_size)]
+    height: Option<usize>,
+
+    /// Aspect ratio of the image, as a number or `width:height`
+    /// [default: depends on the scene]
+    #[arg(long, value_parser = parse_aspect_ratio)]
+    aspect_ratio: Option<f64>,
+
+    /// Number of rays traced through each pixel [default: 50]
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..))]
+    samples: Option<u64>,
+
+    /// Most bounces a ray can take. Paths usually end well before
+    /// this, from Russian roulette [default: 50]
+    #[arg(long)]
+    max_depth: Option<u64>,
+
+    /// Position of the camera, as `x,y,z` [default: depends on the scene]
+    #[arg(long, value_parser = parse_point, allow_hyphen_values = true)]
+    look_from: Option<Point3>,
+
+    /// Point the camera looks at, as `x,y,z` [default: depends on the scene]
+    #[arg(long, value_parser = parse_point, allow_hyphen_values = true)]
+    look_at: Option<Point3>,
+
+    /// Vertical field of view, in degrees [default: depends on the scene]
+    #[arg(long)]
+    fov: Option<f64>,
+
+    /// Diameter of the camera's lens. Zero keeps everything
+    /// in focus [default: depends on the scene]
+    #[arg(long)]
+    aperture: Option<f64>,
+
+    /// Distance from the camera to the plane in perfect
+    /// focus [default: depends on the scene]
+    #[arg(long)]
+    focus_dist: Option<f64>,
+
+    /// Rotation of the camera around its view direction, in
+    /// degrees [default: depends on the scene]
+    #[arg(long, allow_hyphen_values = true)]
+    roll: Option<f64>,
+
+    /// File to save the image to.
+    #[arg(short, long, default_value = "image.png")]
+    output: PathBuf,
+
+    /// Image format to save in, like `png` or `jpg` [default: from
+    /// the output file's extension]
+    #[arg(long, value_parser = parse_format)]
+    format: Option<ImageFormat>,
+
+    /// Number of threads to render with [default: one per core]
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Seed for the random numbers, which makes the render
+    /// come out the same every time.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+fn parse_size(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(0) => Err(String::from("the image can't be empty")),
+        Ok(size) => Ok(size),
+        Err(e) => Err(format!("{e}")),
+    }
+}
+
+fn parse_aspect_ratio(s: &str) -> Result<f64, String> {
+    let ratio = match s.split_once(':') {
+        Some((w, h)) => {
+            let w: f64 = w.trim().parse().map_err(|e| format!("{e}"))?;
+            let h: f64 = h.trim().parse().map_err(|e| format!("{e}"))?;
+            w / h
+        }
+        None => s.parse().map_err(|e| format!("{e}"))?,
+    };
+
+    if ratio.is_finite() && ratio > 0.0 {
+        Ok(ratio)
+    } else {
+        Err(String::from("must be a positive number"))
+    }
+}
+
+fn parse_point(s: &str) -> Result<Point3, String> {
+    let coordinates = s
+        .split(',')
+        .map(|c| c.trim().parse::<f64>().map_err(|e| format!("{e}")))
+        .collect::<Result<Vec<f64>, String>>()?;
+
+    match coordinates[..] {
+        [x, y, z] => Ok(Point3::new(x, y, z)),
+        _ => Err(format!(
+            "expected 3 coordinates, found {}",
+            coordinates.len()
+        )),
+    }
+}
+
+fn parse_format(s: &str) -> Result<ImageFormat, String> {
+    ImageFormat::from_extension(s).ok_or_else(|| format!("unknown image format `{s}`"))
+}
+
+impl Args {
+    /// Overrides the camera and render settings with the ones given.
+    fn apply(&self, camera: &mut CameraDescription, render: &mut RenderSettings) {
+        camera.look_from = self.look_from.unwrap_or(camera.look_from);
+        camera.look_at = self.look_at.unwrap_or(camera.look_at);
+        camera.roll = self.roll.unwrap_or(camera.roll);
+        camera.fov = self.fov.unwrap_or(camera.fov);
+        camera.aperture = self.aperture.unwrap_or(camera.aperture);
+        camera.focus_dist = self.focus_dist.or(camera.focus_dist);
+
+        let aspect_ratio = self.aspect_ratio.unwrap_or(render.aspect_ratio());
+        render.width = self.width.unwrap_or(render.width);
+        render.height = match self.height {
+            Some(height) => height,
+            // keep the shape of the image, unless it's been changed
+            None if self.width.is_some() || self.aspect_ratio.is_some() => {
+                ((render.width as f64 / aspect_ratio) as usize).max(1)
+            }
+            None => render.height,
+        };
+        render.samples_per_pixel = self.samples.unwrap_or(render.samples_per_pixel);
+        render.max_depth = self.max_depth.unwrap_or(render.max_depth);
+        render.seed = self.seed.or(render.seed);
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let file = args.file.as_ref().map(|path| {
+        SceneFile::load(path).unwrap_or_else(|e| {
+            eprintln!("error: couldn't read {}: {e}", path.display());
+            process::exit(1);
+        })
+    });
+    if let Some(SceneFile::Imported(imported)) = &file {
+        for warning in &imported.warnings {
+            eprintln!("warning: {warning}");
+        }
+    }
+
+    // camera and image
+    let (mut camera, mut render) = match &file {
+        Some(file) => file.settings(),
+        None => args.scene.defaults(),
+    };
+    args.apply(&mut camera, &mut render);
+
+    if let Some(seed) = render.seed {
+        // the random scene is different every time, too
+        random::reseed(seed);
+    }
+
+    // world
+    let scene = match file {
+        Some(file) => file.build(camera, render.clone()).unwrap_or_else(|e| {
+            eprintln!("error: couldn't build the scene: {e}");
+            process::exit(1);
+        }),
+        None => args.scene.build(camera.build(render.aspect_ratio())),
+    };
+
+    let renderer = render.build().unwrap_or_else(|e| {
+        eprintln!("error: couldn't set up the render: {e}");
+        process::exit(1);
+    });
+    let mut renderer = renderer
+        .with_integrator(Box::new(PathIntegrator::new()))
+        .with_progress(|remaining| eprintln!("Scanlines remaining: {remaining}"));
+    if let Some(threads) = args.threads {
+        renderer = renderer.with_threads(threads);
+    }
+
+    let image = renderer.render(&scene).to_image();
+    let saved = match args.format {
+        Some(format) => image.save_with_format(&args.output, format),
+        None => image.save(&args.output),
+    };
+    if let Err(e) = saved {
+        eprintln!("error: couldn't save {}: {e}", args.output.display());
+        process::exit(1);
+    }
 
     eprintln!("Done.");
 }
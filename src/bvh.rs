@@ -0,0 +1,528 @@
+//! Provides a bounding volume hierarchy, a binary tree
+//! of bounding boxes over a list of objects. A ray only
+//! visits the subtrees whose boxes it passes through,
+//! so finding the closest hit takes logarithmic rather
+//! than linear time in the number of objects.
+//!
+//! Trees can be nested, by putting objects that have their own
+//! trees, like meshes, into another one. Scenes that place the same
+//! mesh many times are best built as a tree over [`Instance`]s,
+//! which all share the mesh and its tree instead of copying them.
+//!
+//! [`Instance`]: super::instance::Instance
+
+use std::fmt::{self, Display};
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::ray::Ray;
+use super::vec::Point3;
+
+/// Relative cost of testing a ray against a node's bounding box,
+/// compared to testing it against one object.
+const TRAVERSAL_COST: f64 = 0.125;
+const INTERSECTION_COST: f64 = 1.0;
+
+/// How the builder decides where to split a node's objects in two.
+#[derive(Clone, Copy, Debug)]
+pub enum SplitMethod {
+    /// Split at the middle of the centroids along the widest axis.
+    /// Fast to build, but can produce poor trees for uneven scenes.
+    Midpoint,
+    /// Bin the centroids along each axis and pick the split with
+    /// the lowest surface area heuristic cost.
+    Sah { bins: usize },
+}
+
+/// Configures how a [`Bvh`] gets built.
+#[derive(Clone, Copy, Debug)]
+pub struct BvhBuilder {
+    split_method: SplitMethod,
+    max_leaf_size: usize,
+}
+
+impl Default for BvhBuilder {
+    fn default() -> Self {
+        Self {
+            split_method: SplitMethod::Midpoint,
+            max_leaf_size: 2,
+        }
+    }
+}
+
+impl BvhBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn split_method(mut self, split_method: SplitMethod) -> Self {
+        self.split_method = split_method;
+        self
+    }
+
+    /// The most objects a leaf will hold before it's forced to split.
+    pub fn max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = max_leaf_size.max(1);
+        self
+    }
+
+    pub fn build<T: Hit>(&self, objects: Vec<T>) -> Bvh<T> {
+        let (objects, unbounded): (Vec<T>, Vec<T>) = objects
+            .into_iter()
+            .partition(|object| object.bounding_box().is_some());
+
+        let bounds: Vec<Aabb> = objects
+            .iter()
+            .map(|object| object.bounding_box().unwrap())
+            .collect();
+        let (tree, order) = BvhTree::build(self, &bounds);
+
+        // move the objects into leaf order
+        let mut slots: Vec<Option<T>> = objects.into_iter().map(Some).collect();
+        let objects = order
+            .into_iter()
+            .map(|i| slots[i].take().unwrap())
+            .collect();
+
+        Bvh {
+            tree,
+            objects,
+            unbounded,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BvhNode {
+    /// Holds `count` objects, starting at index `first`.
+    Leaf {
+        bbox: Aabb,
+        first: usize,
+        count: usize,
+    },
+    /// Holds the indices of its two child nodes.
+    Branch {
+        bbox: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn bbox(&self) -> Aabb {
+        match *self {
+            BvhNode::Leaf { bbox, .. } | BvhNode::Branch { bbox, .. } => bbox,
+        }
+    }
+}
+
+/// The tree itself, without the objects it was built over. Leaves refer
+/// to objects by index, so the tree can be shared by any kind of object
+/// list, like the triangles of a mesh.
+pub(crate) struct BvhTree {
+    /// All nodes of the tree, with the root at index 0.
+    nodes: Vec<BvhNode>,
+}
+
+impl BvhTree {
+    /// Builds a tree over objects with the given bounding boxes. Also returns
+    /// the order the objects need to be rearranged into, so that every leaf
+    /// refers to a contiguous range of them.
+    pub(crate) fn build(options: &BvhBuilder, bounds: &[Aabb]) -> (Self, Vec<usize>) {
+        let mut builder = Builder {
+            options,
+            bounds,
+            nodes: Vec::new(),
+        };
+        let mut order: Vec<usize> = (0..bounds.len()).collect();
+        if !order.is_empty() {
+            builder.build(&mut order, 0);
+        }
+
+        (
+            Self {
+                nodes: builder.nodes,
+            },
+            order,
+        )
+    }
+
+    pub(crate) fn bounding_box(&self) -> Option<Aabb> {
+        self.nodes.first().map(BvhNode::bbox)
+    }
+
+    /// Finds the closest hit along the ray, calling `hit_object` with the
+    /// index of each object the ray might hit and the closest hit so far.
+    pub(crate) fn hit<'a, F>(
+        &self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        mut hit_object: F,
+    ) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f64) -> Option<HitRecord<'a>>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        self.hit_node(0, r, t_min, t_max, &mut hit_object)
+    }
+
+    fn hit_node<'a, F>(
+        &self,
+        index: usize,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        hit_object: &mut F,
+    ) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f64) -> Option<HitRecord<'a>>,
+    {
+        let node = self.nodes[index];
+        if !node.bbox().hit(r, t_min, t_max) {
+            return None;
+        }
+
+        match node {
+            BvhNode::Leaf { first, count, .. } => {
+                let mut temp_rec = None;
+                let mut closest_so_far = t_max;
+
+                for i in first..first + count {
+                    if let Some(rec) = hit_object(i, closest_so_far) {
+                        closest_so_far = rec.t;
+                        temp_rec = Some(rec);
+                    }
+                }
+
+                temp_rec
+            }
+            BvhNode::Branch { left, right, .. } => {
+                let hit_left = self.hit_node(left, r, t_min, t_max, hit_object);
+                let closest_so_far = hit_left.as_ref().map_or(t_max, |rec| rec.t);
+                let hit_right = self.hit_node(right, r, t_min, closest_so_far, hit_object);
+
+                hit_right.or(hit_left)
+            }
+        }
+    }
+
+    pub(crate) fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats {
+            node_count: self.nodes.len(),
+            leaf_count: 0,
+            depth: 0,
+            leaf_sizes: Vec::new(),
+            traversal_cost: 0.0,
+        };
+
+        if let Some(root) = self.nodes.first() {
+            let root_area = root.bbox().surface_area();
+            self.gather_stats(0, 1, root_area, &mut stats);
+        }
+
+        stats
+    }
+
+    fn gather_stats(&self, index: usize, depth: usize, root_area: f64, stats: &mut BvhStats) {
+        let node = self.nodes[index];
+
+        // the chance of a ray passing through a node is
+        // proportional to its share of the root's surface area
+        let probability = if root_area > 0.0 {
+            node.bbox().surface_area() / root_area
+        } else {
+            1.0
+        };
+        stats.depth = stats.depth.max(depth);
+
+        match node {
+            BvhNode::Leaf { count, .. } => {
+                stats.leaf_count += 1;
+                if stats.leaf_sizes.len() <= count {
+                    stats.leaf_sizes.resize(count + 1, 0);
+                }
+                stats.leaf_sizes[count] += 1;
+                stats.traversal_cost += probability * count as f64 * INTERSECTION_COST;
+            }
+            BvhNode::Branch { left, right, .. } => {
+                stats.traversal_cost += probability * TRAVERSAL_COST;
+                self.gather_stats(left, depth + 1, root_area, stats);
+                self.gather_stats(right, depth + 1, root_area, stats);
+            }
+        }
+    }
+}
+
+/// A tree over a list of objects, which are usually boxed
+/// to mix different kinds, but can be all of the same kind.
+pub struct Bvh<T = Box<dyn Hit>> {
+    tree: BvhTree,
+    /// Objects ordered so every leaf refers to a contiguous range.
+    objects: Vec<T>,
+    /// Objects without a bounding box, which every ray has to be tested against.
+    unbounded: Vec<T>,
+}
+
+impl<T: Hit> Bvh<T> {
+    /// Builds a tree with the default [`BvhBuilder`] options.
+    pub fn new(objects: Vec<T>) -> Self {
+        BvhBuilder::new().build(objects)
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = self.tree.stats();
+
+        // unbounded objects get tested by every ray
+        stats.traversal_cost += self.unbounded.len() as f64 * INTERSECTION_COST;
+        stats
+    }
+}
+
+impl<T: Hit> Hit for Bvh<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut hit_unbounded = None;
+        let mut closest_so_far = t_max;
+        for object in &self.unbounded {
+            if let Some(rec) = object.hit(r, t_min, closest_so_far) {
+                closest_so_far = rec.t;
+                hit_unbounded = Some(rec);
+            }
+        }
+
+        self.tree
+            .hit(r, t_min, closest_so_far, |i, closest_so_far| {
+                self.objects[i].hit(r, t_min, closest_so_far)
+            })
+            .or(hit_unbounded)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+
+        self.tree.bounding_box()
+    }
+}
+
+/// Describes the shape of a built tree, for comparing builders.
+#[derive(Clone, Debug)]
+pub struct BvhStats {
+    pub node_count: usize,
+    pub leaf_count: usize,
+    /// Number of nodes on the longest path from the root to a leaf.
+    pub depth: usize,
+    /// `leaf_sizes[n]` is the number of leaves holding `n` objects.
+    pub leaf_sizes: Vec<usize>,
+    /// Expected cost of tracing a random ray through the tree, in units
+    /// of object intersection tests, as estimated by the surface area heuristic.
+    pub traversal_cost: f64,
+}
+
+impl Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} nodes, {} leaves, depth {}, estimated cost {:.2}",
+            self.node_count, self.leaf_count, self.depth, self.traversal_cost
+        )?;
+        write!(f, "leaf sizes:")?;
+        for (size, &count) in self.leaf_sizes.iter().enumerate() {
+            if count > 0 {
+                write!(f, " {size}: {count}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Builder<'a> {
+    options: &'a BvhBuilder,
+    bounds: &'a [Aabb],
+    nodes: Vec<BvhNode>,
+}
+
+impl<'a> Builder<'a> {
+    /// Builds the subtree over `order`, a slice of object indices
+    /// starting at `first` in the final ordering. Returns the
+    /// index of the subtree's root node.
+    fn build(&mut self, order: &mut [usize], first: usize) -> usize {
+        let bbox = order
+            .iter()
+            .fold(Aabb::empty(), |acc, &i| acc.surrounding(self.bounds[i]));
+
+        let index = self.nodes.len();
+        let split = if order.len() == 1 {
+            None
+        } else {
+            match self.options.split_method {
+                SplitMethod::Midpoint if order.len() <= self.options.max_leaf_size => None,
+                SplitMethod::Midpoint => Some(self.split_midpoint(order)),
+                SplitMethod::Sah { bins } => self.split_sah(order, bbox, bins),
+            }
+        };
+
+        let mid = match split {
+            Some(mid) => mid,
+            None => {
+                self.nodes.push(BvhNode::Leaf {
+                    bbox,
+                    first,
+                    count: order.len(),
+                });
+                return index;
+            }
+        };
+
+        // placeholder, filled in once the children are known
+        self.nodes.push(BvhNode::Leaf {
+            bbox,
+            first,
+            count: 0,
+        });
+
+        let (left_order, right_order) = order.split_at_mut(mid);
+        let left = self.build(left_order, first);
+        let right = self.build(right_order, first + mid);
+
+        self.nodes[index] = BvhNode::Branch { bbox, left, right };
+        index
+    }
+
+    fn centroid_bounds(&self, order: &[usize]) -> Aabb {
+        order.iter().fold(Aabb::empty(), |acc, &i| {
+            let c = self.bounds[i].centroid();
+            acc.surrounding(Aabb::new(c, c))
+        })
+    }
+
+    /// Partitions `order` around the middle of its centroids' extent
+    /// along the widest axis, returning the size of the first half.
+    fn split_midpoint(&self, order: &mut [usize]) -> usize {
+        let centroid_bounds = self.centroid_bounds(order);
+        let axis = centroid_bounds.longest_axis();
+        let midpoint = centroid_bounds.centroid()[axis];
+
+        let mid = self.partition(order, |c| c[axis] < midpoint);
+        if mid == 0 || mid == order.len() {
+            // every centroid is on one side, so fall back to splitting
+            // into two equally-sized halves
+            return self.split_median(order, axis);
+        }
+
+        mid
+    }
+
+    /// Partitions `order` at the lowest cost split found by binning the
+    /// centroids along each axis. Returns none if keeping the objects
+    /// together in a leaf is cheaper than any split.
+    fn split_sah(&self, order: &mut [usize], bbox: Aabb, bins: usize) -> Option<usize> {
+        let bins = bins.max(2);
+        let count = order.len();
+        let leaf_cost = count as f64 * INTERSECTION_COST;
+        let area = bbox.surface_area();
+
+        let centroid_bounds = self.centroid_bounds(order);
+        let bin_of = |c: f64, axis: usize| {
+            let min = centroid_bounds.min()[axis];
+            let extent = centroid_bounds.extent()[axis];
+            let b = ((c - min) / extent * bins as f64) as usize;
+            b.min(bins - 1)
+        };
+
+        // (cost, axis, last bin of the left half)
+        let mut best: Option<(f64, usize, usize)> = None;
+        for axis in 0..3 {
+            if centroid_bounds.extent()[axis] <= 0.0 {
+                continue;
+            }
+
+            let mut bin_bounds = vec![Aabb::empty(); bins];
+            let mut bin_counts = vec![0; bins];
+            for &i in order.iter() {
+                let b = bin_of(self.bounds[i].centroid()[axis], axis);
+                bin_bounds[b] = bin_bounds[b].surrounding(self.bounds[i]);
+                bin_counts[b] += 1;
+            }
+
+            // sweep from the right first, so the left sweep can
+            // evaluate every split in a single pass
+            let mut right_area = vec![0.0; bins];
+            let mut right_count = vec![0; bins];
+            let mut acc_bounds = Aabb::empty();
+            let mut acc_count = 0;
+            for b in (1..bins).rev() {
+                acc_bounds = acc_bounds.surrounding(bin_bounds[b]);
+                acc_count += bin_counts[b];
+                right_area[b] = acc_bounds.surface_area();
+                right_count[b] = acc_count;
+            }
+
+            let mut acc_bounds = Aabb::empty();
+            let mut acc_count = 0;
+            for b in 0..bins - 1 {
+                acc_bounds = acc_bounds.surrounding(bin_bounds[b]);
+                acc_count += bin_counts[b];
+                if acc_count == 0 || right_count[b + 1] == 0 {
+                    continue;
+                }
+
+                let cost = TRAVERSAL_COST
+                    + INTERSECTION_COST
+                        * (acc_bounds.surface_area() * acc_count as f64
+                            + right_area[b + 1] * right_count[b + 1] as f64)
+                        / area;
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, b));
+                }
+            }
+        }
+
+        match best {
+            Some((cost, axis, last_bin)) => {
+                if count <= self.options.max_leaf_size && leaf_cost <= cost {
+                    return None;
+                }
+
+                Some(self.partition(order, |c| bin_of(c[axis], axis) <= last_bin))
+            }
+            // every centroid is in the same place, so no split can separate them
+            None if count <= self.options.max_leaf_size => None,
+            None => Some(self.split_median(order, bbox.longest_axis())),
+        }
+    }
+
+    /// Splits `order` into two equally-sized halves along `axis`.
+    fn split_median(&self, order: &mut [usize], axis: usize) -> usize {
+        let mid = order.len() / 2;
+        order.select_nth_unstable_by(mid, |&a, &b| {
+            let ca = self.bounds[a].centroid()[axis];
+            let cb = self.bounds[b].centroid()[axis];
+            ca.total_cmp(&cb)
+        });
+
+        mid
+    }
+
+    /// Moves every object whose centroid satisfies `goes_left` to the
+    /// front of `order`, returning how many of them there are.
+    fn partition<F>(&self, order: &mut [usize], goes_left: F) -> usize
+    where
+        F: Fn(Point3) -> bool,
+    {
+        let mut mid = 0;
+        for i in 0..order.len() {
+            if goes_left(self.bounds[order[i]].centroid()) {
+                order.swap(i, mid);
+                mid += 1;
+            }
+        }
+
+        mid
+    }
+}
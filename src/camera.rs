@@ -1,11 +1,38 @@
 //! Provides an abstraction for a camera.
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::ray::Ray;
 
 use super::vec::{Point3, Vec3};
 
-#[derive(Debug)]
+/// Everything needed to set up a camera: where it is,
+/// where it's looking, and the shape of its lens and image.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CameraParams {
+    pub look_from: Point3,
+    pub look_at: Point3,
+    /// Rotation around the view direction, in degrees.
+    pub roll: f64,
+    /// Vertical field of view, in degrees.
+    pub fov: f64,
+    pub aspect_ratio: f64,
+    pub aperture: f64,
+    /// The distance to the plane in focus.
+    pub focus_dist: f64,
+}
+
+/// A camera, which serializes as the [`CameraParams`] it was created from.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "CameraParams", into = "CameraParams")
+)]
 pub struct Camera {
+    params: CameraParams,
     origin: Point3,
     lower_left_corner: Point3,
     horizontal: Vec3,
@@ -25,6 +52,28 @@ impl Camera {
         aperture: f64,
         focus_dist: f64,
     ) -> Self {
+        Self::from_params(CameraParams {
+            look_from: origin,
+            look_at,
+            roll,
+            fov,
+            aspect_ratio,
+            aperture,
+            focus_dist,
+        })
+    }
+
+    pub fn from_params(params: CameraParams) -> Self {
+        let CameraParams {
+            look_from: origin,
+            look_at,
+            roll,
+            fov,
+            aspect_ratio,
+            aperture,
+            focus_dist,
+        } = params;
+
         // convert to radians
         let roll_angle = roll.to_radians();
         let rotated_up = Vec3::new(-roll_angle.sin(), roll_angle.cos(), 0.0);
@@ -36,12 +85,12 @@ impl Camera {
         let u = rotated_up.cross(w).normalized();
         let v = w.cross(u);
 
-        let origin = origin;
         let horizontal = u * viewport_width * focus_dist;
         let vertical = v * viewport_height * focus_dist;
         let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * w;
 
         Self {
+            params,
             origin,
             horizontal,
             vertical,
@@ -52,6 +101,11 @@ impl Camera {
         }
     }
 
+    /// The parameters the camera was created from.
+    pub fn params(&self) -> &CameraParams {
+        &self.params
+    }
+
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
         let rd = self.lens_radius * Vec3::random_in_unit_disk();
         let offset = self.u * rd.x() + self.v * rd.y();
@@ -64,3 +118,15 @@ impl Camera {
         )
     }
 }
+
+impl From<CameraParams> for Camera {
+    fn from(params: CameraParams) -> Self {
+        Self::from_params(params)
+    }
+}
+
+impl From<Camera> for CameraParams {
+    fn from(camera: Camera) -> Self {
+        camera.params
+    }
+}
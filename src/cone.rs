@@ -0,0 +1,184 @@
+//! Represents a cone with a dynamic material.
+//!
+//! A cone runs between two circles around the same axis. When one
+//! of them has no radius, it comes to a point there, and otherwise
+//! it's cut off, like the reducers joining pipes of different
+//! sizes. Like a cylinder, it's open unless it's given caps.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::disk::{self, Disk};
+use super::hit::{self, Hit, HitRecord};
+use super::material::Scatter;
+use super::math;
+use super::onb::Onb;
+use super::random;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+pub struct Cone {
+    base: Point3,
+    /// Axes for the texture coordinates, with the cone's axis as `w`.
+    axes: Onb,
+    base_radius: f64,
+    top_radius: f64,
+    height: f64,
+    mat: Arc<dyn Scatter>,
+    caps: Vec<Disk>,
+}
+
+impl Cone {
+    /// Creates the cone from the circle of `base_radius` around
+    /// `base` to the circle of `top_radius` around `top`, or returns
+    /// none if the ends are in the same place, either radius is
+    /// negative, or both radii are zero.
+    pub fn new(
+        base: Point3,
+        base_radius: f64,
+        top: Point3,
+        top_radius: f64,
+        mat: Arc<dyn Scatter>,
+    ) -> Option<Self> {
+        let axis = top - base;
+        if axis.length() == 0.0
+            || base_radius < 0.0
+            || top_radius < 0.0
+            || base_radius + top_radius <= 0.0
+        {
+            return None;
+        }
+
+        Some(Self {
+            base,
+            axes: Onb::new(axis),
+            base_radius,
+            top_radius,
+            height: axis.length(),
+            mat,
+            caps: Vec::new(),
+        })
+    }
+
+    /// Closes the ends that have a radius with disks, making the cone solid.
+    pub fn with_caps(mut self) -> Self {
+        let (axis, top) = (self.axes.w(), self.base + self.height * self.axes.w());
+        // a pointed end has no disk to close it with
+        self.caps = [
+            (self.base, -axis, self.base_radius),
+            (top, axis, self.top_radius),
+        ]
+        .into_iter()
+        .filter_map(|(center, normal, radius)| Disk::new(center, normal, radius, self.mat.clone()))
+        .collect();
+        self
+    }
+
+    /// How much the radius grows for each unit along the axis.
+    fn slope(&self) -> f64 {
+        (self.top_radius - self.base_radius) / self.height
+    }
+
+    fn side_area(&self) -> f64 {
+        let slant = (self.top_radius - self.base_radius).hypot(self.height);
+        PI * (self.base_radius + self.top_radius) * slant
+    }
+
+    fn area(&self) -> f64 {
+        self.side_area() + self.caps.iter().map(Disk::area).sum::<f64>()
+    }
+}
+
+impl Hit for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut temp_rec = None;
+        let mut closest_so_far = t_max;
+
+        // the side, with the base at the origin and the axis along +Z,
+        // where the radius at height z is `base_radius + slope * z`
+        let o = self.axes.to_local(r.origin() - self.base);
+        let d = self.axes.to_local(r.direction());
+        let s = self.slope();
+        let radius = self.base_radius + s * o.z();
+        let roots = math::solve_quadratic(
+            d.x() * d.x() + d.y() * d.y() - s * s * d.z() * d.z(),
+            2.0 * (o.x() * d.x() + o.y() * d.y() - s * d.z() * radius),
+            o.x() * o.x() + o.y() * o.y() - radius * radius,
+        );
+        for &t in roots.iter() {
+            let p = o + t * d;
+            // the other half of the double cone is past the apex, out of this range
+            if t < t_min || t > closest_so_far || p.z() < 0.0 || p.z() > self.height {
+                continue;
+            }
+
+            // `u` goes around the axis, and `v` along it from the base to the top
+            let radius = self.base_radius + s * p.z();
+            let outward_normal = self
+                .axes
+                .local(Vec3::new(p.x(), p.y(), -s * radius))
+                .normalized();
+            let mut rec = HitRecord::new(r, t, outward_normal, &*self.mat);
+            rec.u = p.y().atan2(p.x()).rem_euclid(2.0 * PI) / (2.0 * PI);
+            rec.v = p.z() / self.height;
+
+            closest_so_far = t;
+            temp_rec = Some(rec);
+            break;
+        }
+
+        for cap in &self.caps {
+            if let Some(rec) = cap.hit(r, t_min, closest_so_far) {
+                closest_so_far = rec.t;
+                temp_rec = Some(rec);
+            }
+        }
+
+        temp_rec
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // the cone is the hull of its two end circles
+        let axis = self.axes.w();
+        let base = disk::circle_bounds(self.base, axis, self.base_radius);
+        let top = disk::circle_bounds(self.base + self.height * axis, axis, self.top_radius);
+        Some(base.surrounding(top))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        hit::area_pdf_value(self, self.area(), origin, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let mut rng = random::thread_rng();
+        let mut x = rng.gen::<f64>() * self.area();
+        if self.caps.is_empty() || x < self.side_area() {
+            // the side has more area where it's wider, so the
+            // radius is picked with density proportional to it
+            let (b, t) = (self.base_radius, self.top_radius);
+            let (r1, r2): (f64, f64) = (rng.gen(), rng.gen());
+            let (radius, z) = if b == t {
+                (b, self.height * r1)
+            } else {
+                let radius = (b * b + r1 * (t * t - b * b)).sqrt();
+                (radius, (radius - b) / self.slope())
+            };
+            let phi = 2.0 * PI * r2;
+            let p = Vec3::new(radius * phi.cos(), radius * phi.sin(), z);
+            return self.base + self.axes.local(p) - origin;
+        }
+
+        // the caps can be different sizes
+        x -= self.side_area();
+        for cap in &self.caps {
+            if x < cap.area() {
+                return cap.random_point() - origin;
+            }
+            x -= cap.area();
+        }
+        self.caps[self.caps.len() - 1].random_point() - origin
+    }
+}
@@ -0,0 +1,87 @@
+//! Represents a box with a dynamic material, made of six quads.
+//!
+//! The box's sides are lined up with the axes. To turn it,
+//! place it in the scene as an [`Instance`].
+//!
+//! [`Instance`]: super::instance::Instance
+
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::quad::Quad;
+use super::random;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+pub struct Cuboid {
+    /// The sides, each facing out of the box.
+    sides: [Quad; 6],
+    bbox: Aabb,
+}
+
+impl Cuboid {
+    /// Creates the box between two opposite corners. Each side
+    /// has its own texture coordinates, from 0 to 1 across it.
+    /// Returns none if the corners line up along any axis, which
+    /// would make the box flat.
+    pub fn new(a: Point3, b: Point3, mat: Arc<dyn Scatter>) -> Option<Self> {
+        let (min, max) = (a.min(b), a.max(b));
+        let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+        let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+        let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+        let sides = [
+            Quad::new(Point3::new(min.x(), min.y(), max.z()), dx, dy, mat.clone())?,
+            Quad::new(Point3::new(max.x(), min.y(), max.z()), -dz, dy, mat.clone())?,
+            Quad::new(Point3::new(max.x(), min.y(), min.z()), -dx, dy, mat.clone())?,
+            Quad::new(Point3::new(min.x(), min.y(), min.z()), dz, dy, mat.clone())?,
+            Quad::new(Point3::new(min.x(), max.y(), max.z()), dx, -dz, mat.clone())?,
+            Quad::new(Point3::new(min.x(), min.y(), min.z()), dx, dz, mat)?,
+        ];
+
+        Some(Self {
+            sides,
+            bbox: Aabb::new(min, max),
+        })
+    }
+}
+
+impl Hit for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut temp_rec = None;
+        let mut closest_so_far = t_max;
+
+        for side in &self.sides {
+            if let Some(rec) = side.hit(r, t_min, closest_so_far) {
+                closest_so_far = rec.t;
+                temp_rec = Some(rec);
+            }
+        }
+
+        temp_rec
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+
+    /// The density of picking `direction` from any of the sides,
+    /// since `random` picks one of them uniformly at random.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let sum: f64 = self
+            .sides
+            .iter()
+            .map(|side| side.pdf_value(origin, direction))
+            .sum();
+        sum / self.sides.len() as f64
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let i = random::thread_rng().gen_range(0..self.sides.len());
+        self.sides[i].random(origin)
+    }
+}
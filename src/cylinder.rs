@@ -0,0 +1,133 @@
+//! Represents a cylinder with a dynamic material.
+//!
+//! A cylinder is an open tube unless it's given caps, which
+//! close its ends with disks of the same material.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::disk::{self, Disk};
+use super::hit::{self, Hit, HitRecord};
+use super::material::Scatter;
+use super::math;
+use super::onb::Onb;
+use super::random;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+pub struct Cylinder {
+    base: Point3,
+    /// Axes for the texture coordinates, with the cylinder's axis as `w`.
+    axes: Onb,
+    radius: f64,
+    height: f64,
+    mat: Arc<dyn Scatter>,
+    caps: Vec<Disk>,
+}
+
+impl Cylinder {
+    /// Creates the tube of the given radius running from `base` to `top`,
+    /// or returns none if the ends are in the same place or the radius
+    /// isn't positive.
+    pub fn new(base: Point3, top: Point3, radius: f64, mat: Arc<dyn Scatter>) -> Option<Self> {
+        let axis = top - base;
+        if axis.length() == 0.0 || radius <= 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            base,
+            axes: Onb::new(axis),
+            radius,
+            height: axis.length(),
+            mat,
+            caps: Vec::new(),
+        })
+    }
+
+    /// Closes both ends with disks, making the cylinder solid.
+    pub fn with_caps(mut self) -> Self {
+        let (axis, top) = (self.axes.w(), self.base + self.height * self.axes.w());
+        self.caps = [(self.base, -axis), (top, axis)]
+            .into_iter()
+            .filter_map(|(center, normal)| Disk::new(center, normal, self.radius, self.mat.clone()))
+            .collect();
+        self
+    }
+
+    fn side_area(&self) -> f64 {
+        2.0 * PI * self.radius * self.height
+    }
+
+    fn area(&self) -> f64 {
+        self.side_area() + self.caps.iter().map(Disk::area).sum::<f64>()
+    }
+}
+
+impl Hit for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut temp_rec = None;
+        let mut closest_so_far = t_max;
+
+        // the side, with the base at the origin and the axis along +Z
+        let o = self.axes.to_local(r.origin() - self.base);
+        let d = self.axes.to_local(r.direction());
+        let roots = math::solve_quadratic(
+            d.x() * d.x() + d.y() * d.y(),
+            2.0 * (o.x() * d.x() + o.y() * d.y()),
+            o.x() * o.x() + o.y() * o.y() - self.radius * self.radius,
+        );
+        for &t in roots.iter() {
+            let p = o + t * d;
+            if t < t_min || t > closest_so_far || p.z() < 0.0 || p.z() > self.height {
+                continue;
+            }
+
+            // `u` goes around the axis, and `v` along it from the base to the top
+            let outward_normal = self.axes.local(Vec3::new(p.x(), p.y(), 0.0) / self.radius);
+            let mut rec = HitRecord::new(r, t, outward_normal, &*self.mat);
+            rec.u = p.y().atan2(p.x()).rem_euclid(2.0 * PI) / (2.0 * PI);
+            rec.v = p.z() / self.height;
+
+            closest_so_far = t;
+            temp_rec = Some(rec);
+            break;
+        }
+
+        for cap in &self.caps {
+            if let Some(rec) = cap.hit(r, t_min, closest_so_far) {
+                closest_so_far = rec.t;
+                temp_rec = Some(rec);
+            }
+        }
+
+        temp_rec
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let axis = self.axes.w();
+        let base = disk::circle_bounds(self.base, axis, self.radius);
+        let top = disk::circle_bounds(self.base + self.height * axis, axis, self.radius);
+        Some(base.surrounding(top))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        hit::area_pdf_value(self, self.area(), origin, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let mut rng = random::thread_rng();
+        if self.caps.is_empty() || rng.gen::<f64>() * self.area() < self.side_area() {
+            let phi = 2.0 * PI * rng.gen::<f64>();
+            let z = self.height * rng.gen::<f64>();
+            let p = Vec3::new(self.radius * phi.cos(), self.radius * phi.sin(), z);
+            return self.base + self.axes.local(p) - origin;
+        }
+
+        // both caps are the same size
+        self.caps[rng.gen_range(0..self.caps.len())].random_point() - origin
+    }
+}
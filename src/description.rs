@@ -0,0 +1,560 @@
+//! Describes scenes as plain data: the camera, the render
+//! settings, named materials, and the shapes that use them.
+//! A description can be read from a text file with the `parse`
+//! submodule, and then built into a [`Scene`] to render.
+//!
+//! With the `serde` feature, descriptions can also be serialized,
+//! so scenes can be saved to and loaded from formats like JSON or RON.
+
+pub mod parse;
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use image::ImageError;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::background::{Background, EnvironmentMap, Gradient, SolidColor};
+use super::bvh::{BvhBuilder, SplitMethod};
+use super::camera::{Camera, CameraParams};
+use super::cone::Cone;
+use super::cuboid::Cuboid;
+use super::cylinder::Cylinder;
+use super::disk::{Annulus, Disk};
+use super::hit::{Hit, World};
+use super::material::{Dielectric, DiffuseLight, Lambertian, Metal, Scatter};
+use super::mesh::{obj, ply, stl, MeshError};
+use super::plane::Plane;
+use super::quad::Quad;
+use super::renderer::Renderer;
+use super::scene::Scene;
+use super::sphere::Sphere;
+use super::torus::Torus;
+use super::triangle::Triangle;
+use super::vec::{Color, Point3, Vec3};
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub render: RenderSettings,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub background: BackgroundDescription,
+    /// Materials by name, for the shapes to refer to.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub shapes: Vec<ShapeDescription>,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CameraDescription {
+    pub look_from: Point3,
+    pub look_at: Point3,
+    /// Rotation around the view direction, in degrees.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub roll: f64,
+    /// Vertical field of view, in degrees.
+    #[cfg_attr(feature = "serde", serde(default = "default_fov"))]
+    pub fov: f64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub aperture: f64,
+    /// The distance to the plane in focus, or none
+    /// to focus on the point being looked at.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub focus_dist: Option<f64>,
+}
+
+fn default_fov() -> f64 {
+    40.0
+}
+
+impl CameraDescription {
+    /// Creates a pinhole camera with a 40 degree field of view.
+    pub fn new(look_from: Point3, look_at: Point3) -> Self {
+        Self {
+            look_from,
+            look_at,
+            roll: 0.0,
+            fov: default_fov(),
+            aperture: 0.0,
+            focus_dist: None,
+        }
+    }
+
+    /// Creates the camera, for an image of the given shape.
+    pub fn build(&self, aspect_ratio: f64) -> Camera {
+        let focus_dist = self
+            .focus_dist
+            .unwrap_or_else(|| (self.look_at - self.look_from).length());
+
+        Camera::from_params(CameraParams {
+            look_from: self.look_from,
+            look_at: self.look_at,
+            roll: self.roll,
+            fov: self.fov,
+            aspect_ratio,
+            aperture: self.aperture,
+            focus_dist,
+        })
+    }
+}
+
+impl From<CameraParams> for CameraDescription {
+    /// Describes the camera the parameters set up,
+    /// leaving the aspect ratio to the render settings.
+    fn from(params: CameraParams) -> Self {
+        Self {
+            look_from: params.look_from,
+            look_at: params.look_at,
+            roll: params.roll,
+            fov: params.fov,
+            aperture: params.aperture,
+            focus_dist: Some(params.focus_dist),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: u64,
+    pub max_depth: u64,
+    /// Makes the render repeatable, if set.
+    pub seed: Option<u64>,
+}
+
+impl RenderSettings {
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    /// Creates a renderer with the settings, or fails if
+    /// the image is empty or a pixel gets no samples.
+    pub fn build(&self) -> Result<Renderer, DescriptionError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(DescriptionError::Invalid("the image can't be empty".into()));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err(DescriptionError::Invalid(
+                "each pixel needs at least one sample".into(),
+            ));
+        }
+
+        let renderer = Renderer::new(self.width, self.height)
+            .with_samples_per_pixel(self.samples_per_pixel)
+            .with_max_depth(self.max_depth);
+
+        Ok(match self.seed {
+            Some(seed) => renderer.with_seed(seed),
+            None => renderer,
+        })
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 450,
+            samples_per_pixel: 50,
+            max_depth: 50,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum BackgroundDescription {
+    /// The default white to light blue sky.
+    #[default]
+    Sky,
+    Color(Color),
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+    /// An environment map, loaded from an image file.
+    Map(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum MaterialDescription {
+    Lambertian { albedo: Color },
+    Metal { albedo: Color, fuzz: f64 },
+    Dielectric { index_of_refraction: f64 },
+    Light { emit: Color },
+}
+
+impl MaterialDescription {
+    pub fn build(&self) -> Arc<dyn Scatter> {
+        match *self {
+            MaterialDescription::Lambertian { albedo } => Arc::new(Lambertian::new(albedo)),
+            MaterialDescription::Metal { albedo, fuzz } => Arc::new(Metal::new(albedo, fuzz)),
+            MaterialDescription::Dielectric {
+                index_of_refraction,
+            } => Arc::new(Dielectric::new(index_of_refraction)),
+            MaterialDescription::Light { emit } => Arc::new(DiffuseLight::new(emit)),
+        }
+    }
+
+    /// Returns whether shapes made of the material give off light.
+    pub fn is_light(&self) -> bool {
+        matches!(self, MaterialDescription::Light { .. })
+    }
+}
+
+/// A shape, and the name of the material it's made of.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ShapeDescription {
+    Sphere {
+        center: Point3,
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [Point3; 3],
+        material: String,
+    },
+    /// A parallelogram with a corner at `corner`, spanning the edges `u` and `v`.
+    Quad {
+        corner: Point3,
+        u: Vec3,
+        v: Vec3,
+        material: String,
+    },
+    /// An infinite plane through `point`.
+    Plane {
+        point: Point3,
+        normal: Vec3,
+        material: String,
+    },
+    /// A box lined up with the axes, between two opposite corners.
+    Cuboid {
+        min: Point3,
+        max: Point3,
+        material: String,
+    },
+    /// A round, flat disk whose front face points along `normal`.
+    Disk {
+        center: Point3,
+        normal: Vec3,
+        radius: f64,
+        material: String,
+    },
+    /// A disk with a round hole in the middle.
+    Annulus {
+        center: Point3,
+        normal: Vec3,
+        inner_radius: f64,
+        outer_radius: f64,
+        material: String,
+    },
+    /// A tube from `base` to `top`, closed at both ends if `capped`.
+    Cylinder {
+        base: Point3,
+        top: Point3,
+        radius: f64,
+        #[cfg_attr(feature = "serde", serde(default))]
+        capped: bool,
+        material: String,
+    },
+    /// A cone between two circles, which comes to a point at
+    /// either end with no radius, and is closed if `capped`.
+    Cone {
+        base: Point3,
+        base_radius: f64,
+        top: Point3,
+        top_radius: f64,
+        #[cfg_attr(feature = "serde", serde(default))]
+        capped: bool,
+        material: String,
+    },
+    /// A ring-shaped tube around `center`, whose hole runs along `axis`.
+    Torus {
+        center: Point3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: String,
+    },
+    /// A mesh loaded from an OBJ, PLY or STL file, picked by its extension.
+    Mesh { path: PathBuf, material: String },
+}
+
+impl ShapeDescription {
+    pub fn material(&self) -> &str {
+        match self {
+            ShapeDescription::Sphere { material, .. }
+            | ShapeDescription::Triangle { material, .. }
+            | ShapeDescription::Quad { material, .. }
+            | ShapeDescription::Plane { material, .. }
+            | ShapeDescription::Cuboid { material, .. }
+            | ShapeDescription::Disk { material, .. }
+            | ShapeDescription::Annulus { material, .. }
+            | ShapeDescription::Cylinder { material, .. }
+            | ShapeDescription::Cone { material, .. }
+            | ShapeDescription::Torus { material, .. }
+            | ShapeDescription::Mesh { material, .. } => material,
+        }
+    }
+
+    fn build(&self, mat: Arc<dyn Scatter>) -> Result<Arc<dyn Hit>, DescriptionError> {
+        Ok(match self {
+            ShapeDescription::Sphere { center, radius, .. } => {
+                Arc::new(Sphere::new(*center, *radius, mat))
+            }
+            ShapeDescription::Triangle { vertices, .. } => {
+                Arc::new(Triangle::new(vertices[0], vertices[1], vertices[2], mat))
+            }
+            ShapeDescription::Quad { corner, u, v, .. } => {
+                Arc::new(Quad::new(*corner, *u, *v, mat).ok_or_else(|| degenerate("quad"))?)
+            }
+            ShapeDescription::Plane { point, normal, .. } => {
+                Arc::new(Plane::new(*point, *normal, mat).ok_or_else(|| degenerate("plane"))?)
+            }
+            ShapeDescription::Cuboid { min, max, .. } => {
+                Arc::new(Cuboid::new(*min, *max, mat).ok_or_else(|| degenerate("cuboid"))?)
+            }
+            ShapeDescription::Disk {
+                center,
+                normal,
+                radius,
+                ..
+            } => Arc::new(
+                Disk::new(*center, *normal, *radius, mat).ok_or_else(|| degenerate("disk"))?,
+            ),
+            ShapeDescription::Annulus {
+                center,
+                normal,
+                inner_radius,
+                outer_radius,
+                ..
+            } => Arc::new(
+                Annulus::new(*center, *normal, *inner_radius, *outer_radius, mat)
+                    .ok_or_else(|| degenerate("annulus"))?,
+            ),
+            ShapeDescription::Cylinder {
+                base,
+                top,
+                radius,
+                capped,
+                ..
+            } => {
+                let cylinder = Cylinder::new(*base, *top, *radius, mat)
+                    .ok_or_else(|| degenerate("cylinder"))?;
+                Arc::new(if *capped {
+                    cylinder.with_caps()
+                } else {
+                    cylinder
+                })
+            }
+            ShapeDescription::Cone {
+                base,
+                base_radius,
+                top,
+                top_radius,
+                capped,
+                ..
+            } => {
+                let cone = Cone::new(*base, *base_radius, *top, *top_radius, mat)
+                    .ok_or_else(|| degenerate("cone"))?;
+                Arc::new(if *capped { cone.with_caps() } else { cone })
+            }
+            ShapeDescription::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+                ..
+            } => Arc::new(
+                Torus::new(*center, *axis, *major_radius, *minor_radius, mat)
+                    .ok_or_else(|| degenerate("torus"))?,
+            ),
+            ShapeDescription::Mesh { path, .. } => {
+                let extension = path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .map(str::to_ascii_lowercase);
+                let mesh = match extension.as_deref() {
+                    Some("obj") => obj::load(path, mat),
+                    Some("ply") => ply::load(path, mat),
+                    Some("stl") => stl::load(path, mat),
+                    _ => Err(MeshError::Format(String::from(
+                        "unknown mesh format, expected an OBJ, PLY or STL file",
+                    ))),
+                };
+                let mesh = mesh.map_err(|error| DescriptionError::Mesh {
+                    path: path.clone(),
+                    error,
+                })?;
+                Arc::new(mesh)
+            }
+        })
+    }
+}
+
+/// The error for a shape whose sizes don't make a shape, like a disk
+/// with no radius. Scene files catch these while they're parsed,
+/// where they can say which line is wrong.
+fn degenerate(shape: &str) -> DescriptionError {
+    DescriptionError::Invalid(format!(
+        "the sizes given for the {shape} don't make a shape"
+    ))
+}
+
+impl SceneDescription {
+    /// Creates an empty scene under the sky, with default render settings.
+    pub fn new(camera: CameraDescription) -> Self {
+        Self {
+            camera,
+            render: RenderSettings::default(),
+            background: BackgroundDescription::default(),
+            materials: BTreeMap::new(),
+            shapes: Vec::new(),
+        }
+    }
+
+    /// Creates the scene, loading any files it refers to. Every bounded
+    /// shape made of a light material is sampled directly, as a light.
+    pub fn build(&self) -> Result<Scene, DescriptionError> {
+        let materials: HashMap<&str, Arc<dyn Scatter>> = self
+            .materials
+            .iter()
+            .map(|(name, mat)| (name.as_str(), mat.build()))
+            .collect();
+
+        let mut world = World::new();
+        let mut lights = World::new();
+        for shape in &self.shapes {
+            let name = shape.material();
+            let mat = materials
+                .get(name)
+                .ok_or_else(|| DescriptionError::UnknownMaterial(name.to_string()))?;
+
+            let object = shape.build(mat.clone())?;
+            // there's no picking points on shapes that go on forever
+            if self.materials[name].is_light() && object.bounding_box().is_some() {
+                lights.push(Box::new(object.clone()));
+            }
+            world.push(Box::new(object));
+        }
+
+        let background: Box<dyn Background> = match &self.background {
+            BackgroundDescription::Sky => Box::new(Gradient::sky()),
+            BackgroundDescription::Color(color) => Box::new(SolidColor::new(*color)),
+            BackgroundDescription::Gradient { bottom, top } => {
+                Box::new(Gradient::new(*bottom, *top))
+            }
+            BackgroundDescription::Map(path) => Box::new(EnvironmentMap::load(path).map_err(
+                |error| DescriptionError::Image {
+                    path: path.clone(),
+                    error,
+                },
+            )?),
+        };
+
+        let world = BvhBuilder::new()
+            .split_method(SplitMethod::Sah { bins: 16 })
+            .max_leaf_size(4)
+            .build(world);
+
+        let camera = self.camera.build(self.render.aspect_ratio());
+        Ok(Scene::new(Box::new(world), camera)
+            .with_background(background)
+            .with_lights(lights))
+    }
+}
+
+/// An error encountered while reading a scene description,
+/// or while building the scene it describes.
+#[derive(Debug)]
+pub enum DescriptionError {
+    Io(io::Error),
+    /// The description is malformed, as found on the given (1-based) line.
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The description as a whole is incomplete or inconsistent.
+    Invalid(String),
+    /// A shape is made of a material that was never defined.
+    UnknownMaterial(String),
+    /// A mesh the scene refers to failed to load.
+    Mesh {
+        path: PathBuf,
+        error: MeshError,
+    },
+    /// An image the scene refers to failed to load.
+    Image {
+        path: PathBuf,
+        error: ImageError,
+    },
+}
+
+impl DescriptionError {
+    pub(crate) fn parse(line: usize, message: impl Into<String>) -> Self {
+        Self::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for DescriptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DescriptionError::Io(e) => write!(f, "{e}"),
+            DescriptionError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            DescriptionError::Invalid(message) => write!(f, "{message}"),
+            DescriptionError::UnknownMaterial(name) => write!(f, "unknown material `{name}`"),
+            DescriptionError::Mesh { path, error } => write!(f, "in {}: {error}", path.display()),
+            DescriptionError::Image { path, error } => {
+                write!(f, "in {}: {error}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for DescriptionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DescriptionError::Io(e) => Some(e),
+            DescriptionError::Parse { .. }
+            | DescriptionError::Invalid(_)
+            | DescriptionError::UnknownMaterial(_) => None,
+            DescriptionError::Mesh { error, .. } => Some(error),
+            DescriptionError::Image { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for DescriptionError {
+    fn from(e: io::Error) -> Self {
+        DescriptionError::Io(e)
+    }
+}
@@ -0,0 +1,671 @@
+//! Parses scene descriptions from a line-based text format.
+//!
+//! Every line is a statement: a keyword, followed by properties, each
+//! a name and its values. Colors and points are three numbers, paths
+//! can be quoted if they contain spaces, and `#` starts a comment.
+//!
+//! ```text
+//! camera look_from 13 2 3 look_at 0 0 0 fov 20 aperture 0.1
+//! render width 800 height 450 samples 100 max_depth 50 seed 1
+//! background sky
+//!
+//! material ground lambertian albedo 0.5 0.5 0.5
+//! material gold metal albedo 0.8 0.6 0.2 fuzz 0.3
+//! material glass dielectric ior 1.5
+//! material lamp light emit 4 4 4
+//!
+//! plane point 0 0 0 normal 0 1 0 material ground
+//! sphere center 0 1 0 radius 1 material glass
+//! triangle v0 -1 3 0 v1 1 3 0 v2 0 3 1 material lamp
+//! quad corner -3 0 -2 u 6 0 0 v 0 3 0 material ground
+//! cuboid min 2 0 0 max 3 1 1 material gold
+//! cylinder base -2 0 1 top -2 2 1 radius 0.3 capped material gold
+//! cone base 3 0 -1 base_radius 0.5 top 3 1 -1 top_radius 0 material ground
+//! disk center 0 2.99 0 normal 0 -1 0 radius 0.5 material lamp
+//! annulus center 0 0.01 0 normal 0 1 0 inner_radius 1 outer_radius 1.5 material gold
+//! torus center 0 1 0 axis 0 1 0 major_radius 2 minor_radius 0.2 material gold
+//! mesh path "models/teapot.obj" material gold
+//! ```
+//!
+//! The backgrounds are `sky`, `color r g b`, `gradient r g b r g b`
+//! (bottom then top) and `map path`, for an environment map. The
+//! camera also takes `roll` and `focus_dist`. Cylinders and cones are
+//! open tubes unless they're `capped`, and a cone with a `top_radius`
+//! of zero comes to a point. Materials have to be
+//! defined before the shapes that use them, and relative paths are
+//! looked up relative to the scene file.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use super::{
+    BackgroundDescription, CameraDescription, DescriptionError, MaterialDescription,
+    RenderSettings, SceneDescription, ShapeDescription,
+};
+use crate::vec::{Color, Point3, Vec3};
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneDescription, DescriptionError> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    parse(BufReader::new(file), dir)
+}
+
+/// Parses a scene description from a reader, looking up
+/// relative paths relative to `dir`.
+pub fn parse<R: BufRead, P: Into<PathBuf>>(
+    reader: R,
+    dir: P,
+) -> Result<SceneDescription, DescriptionError> {
+    let mut parser = Parser::new(dir.into());
+    for (i, line) in reader.lines().enumerate() {
+        parser.line = i + 1;
+        parser.parse_line(&line?)?;
+    }
+
+    parser.finish()
+}
+
+/// The values of a statement's properties, by name.
+type Properties<'t> = HashMap<&'static str, &'t [String]>;
+
+struct Parser {
+    /// The line currently being parsed.
+    line: usize,
+    dir: PathBuf,
+    look_from: Option<Point3>,
+    look_at: Option<Point3>,
+    camera: CameraDescription,
+    render: RenderSettings,
+    background: BackgroundDescription,
+    materials: Vec<(String, MaterialDescription)>,
+    shapes: Vec<ShapeDescription>,
+}
+
+impl Parser {
+    fn new(dir: PathBuf) -> Self {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        Self {
+            line: 0,
+            dir,
+            look_from: None,
+            look_at: None,
+            camera: CameraDescription::new(origin, origin),
+            render: RenderSettings::default(),
+            background: BackgroundDescription::default(),
+            materials: Vec::new(),
+            shapes: Vec::new(),
+        }
+    }
+
+    fn finish(self) -> Result<SceneDescription, DescriptionError> {
+        let (look_from, look_at) = match (self.look_from, self.look_at) {
+            (Some(look_from), Some(look_at)) => (look_from, look_at),
+            _ => {
+                return Err(DescriptionError::Invalid(String::from(
+                    "the camera needs both `look_from` and `look_at`",
+                )))
+            }
+        };
+
+        let mut scene = SceneDescription::new(CameraDescription {
+            look_from,
+            look_at,
+            ..self.camera
+        });
+        scene.render = self.render;
+        scene.background = self.background;
+        scene.materials = self.materials.into_iter().collect();
+        scene.shapes = self.shapes;
+
+        Ok(scene)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), DescriptionError> {
+        let tokens = self.tokenize(line)?;
+        let (keyword, args) = match tokens.split_first() {
+            Some((keyword, args)) => (keyword.as_str(), args),
+            None => return Ok(()),
+        };
+
+        match keyword {
+            "camera" => self.camera(args),
+            "render" => self.render(args),
+            "background" => self.background(args),
+            "material" => self.material(args),
+            "sphere" => {
+                let props = self.properties(
+                    args,
+                    &[("center", 3), ("radius", 1), ("material", 1)],
+                    &["center", "radius", "material"],
+                )?;
+                self.shapes.push(ShapeDescription::Sphere {
+                    center: self.vector(&props, "center")?.unwrap(),
+                    radius: self.number(&props, "radius")?.unwrap(),
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            "triangle" => {
+                let props = self.properties(
+                    args,
+                    &[("v0", 3), ("v1", 3), ("v2", 3), ("material", 1)],
+                    &["v0", "v1", "v2", "material"],
+                )?;
+                self.shapes.push(ShapeDescription::Triangle {
+                    vertices: [
+                        self.vector(&props, "v0")?.unwrap(),
+                        self.vector(&props, "v1")?.unwrap(),
+                        self.vector(&props, "v2")?.unwrap(),
+                    ],
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            "quad" => {
+                let props = self.properties(
+                    args,
+                    &[("corner", 3), ("u", 3), ("v", 3), ("material", 1)],
+                    &["corner", "u", "v", "material"],
+                )?;
+                let u = self.vector(&props, "u")?.unwrap();
+                let v = self.vector(&props, "v")?.unwrap();
+                if u.cross(v).length() == 0.0 {
+                    return Err(self.error("the edges of a quad can't be zero or parallel"));
+                }
+                self.shapes.push(ShapeDescription::Quad {
+                    corner: self.vector(&props, "corner")?.unwrap(),
+                    u,
+                    v,
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            "plane" => {
+                let props = self.properties(
+                    args,
+                    &[("point", 3), ("normal", 3), ("material", 1)],
+                    &["point", "normal", "material"],
+                )?;
+                let normal = self.vector(&props, "normal")?.unwrap();
+                if normal.length() == 0.0 {
+                    return Err(self.error("the normal of a plane can't be zero"));
+                }
+                self.shapes.push(ShapeDescription::Plane {
+                    point: self.vector(&props, "point")?.unwrap(),
+                    normal,
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            "cuboid" => {
+                let props = self.properties(
+                    args,
+                    &[("min", 3), ("max", 3), ("material", 1)],
+                    &["min", "max", "material"],
+                )?;
+                let min = self.vector(&props, "min")?.unwrap();
+                let max = self.vector(&props, "max")?.unwrap();
+                let extent = max - min;
+                if extent.x() == 0.0 || extent.y() == 0.0 || extent.z() == 0.0 {
+                    return Err(self.error("a cuboid can't be flat"));
+                }
+                self.shapes.push(ShapeDescription::Cuboid {
+                    min,
+                    max,
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            "disk" => {
+                let props = self.properties(
+                    args,
+                    &[("center", 3), ("normal", 3), ("radius", 1), ("material", 1)],
+                    &["center", "normal", "radius", "material"],
+                )?;
+                let normal = self.vector(&props, "normal")?.unwrap();
+                let radius = self.number(&props, "radius")?.unwrap();
+                if normal.length() == 0.0 {
+                    return Err(self.error("the normal of a disk can't be zero"));
+                }
+                if radius <= 0.0 {
+                    return Err(self.error("the radius of a disk has to be positive"));
+                }
+                self.shapes.push(ShapeDescription::Disk {
+                    center: self.vector(&props, "center")?.unwrap(),
+                    normal,
+                    radius,
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            "annulus" => {
+                let props = self.properties(
+                    args,
+                    &[
+                        ("center", 3),
+                        ("normal", 3),
+                        ("inner_radius", 1),
+                        ("outer_radius", 1),
+                        ("material", 1),
+                    ],
+                    &[
+                        "center",
+                        "normal",
+                        "inner_radius",
+                        "outer_radius",
+                        "material",
+                    ],
+                )?;
+                let normal = self.vector(&props, "normal")?.unwrap();
+                let inner_radius = self.number(&props, "inner_radius")?.unwrap();
+                let outer_radius = self.number(&props, "outer_radius")?.unwrap();
+                if normal.length() == 0.0 {
+                    return Err(self.error("the normal of an annulus can't be zero"));
+                }
+                if inner_radius < 0.0 || inner_radius >= outer_radius {
+                    return Err(self.error(
+                        "the inner radius of an annulus has to be at least 0, and less than the outer radius",
+                    ));
+                }
+                self.shapes.push(ShapeDescription::Annulus {
+                    center: self.vector(&props, "center")?.unwrap(),
+                    normal,
+                    inner_radius,
+                    outer_radius,
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            "cylinder" => {
+                let props = self.properties(
+                    args,
+                    &[
+                        ("base", 3),
+                        ("top", 3),
+                        ("radius", 1),
+                        ("capped", 0),
+                        ("material", 1),
+                    ],
+                    &["base", "top", "radius", "material"],
+                )?;
+                let base = self.vector(&props, "base")?.unwrap();
+                let top = self.vector(&props, "top")?.unwrap();
+                let radius = self.number(&props, "radius")?.unwrap();
+                if (top - base).length() == 0.0 {
+                    return Err(self.error("the ends of a cylinder can't be in the same place"));
+                }
+                if radius <= 0.0 {
+                    return Err(self.error("the radius of a cylinder has to be positive"));
+                }
+                self.shapes.push(ShapeDescription::Cylinder {
+                    base,
+                    top,
+                    radius,
+                    capped: props.contains_key("capped"),
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            "cone" => {
+                let props = self.properties(
+                    args,
+                    &[
+                        ("base", 3),
+                        ("base_radius", 1),
+                        ("top", 3),
+                        ("top_radius", 1),
+                        ("capped", 0),
+                        ("material", 1),
+                    ],
+                    &["base", "base_radius", "top", "top_radius", "material"],
+                )?;
+                let base = self.vector(&props, "base")?.unwrap();
+                let base_radius = self.number(&props, "base_radius")?.unwrap();
+                let top = self.vector(&props, "top")?.unwrap();
+                let top_radius = self.number(&props, "top_radius")?.unwrap();
+                if (top - base).length() == 0.0 {
+                    return Err(self.error("the ends of a cone can't be in the same place"));
+                }
+                if base_radius < 0.0 || top_radius < 0.0 {
+                    return Err(self.error("the radii of a cone can't be negative"));
+                }
+                if base_radius == 0.0 && top_radius == 0.0 {
+                    return Err(self.error("one of the radii of a cone has to be positive"));
+                }
+                self.shapes.push(ShapeDescription::Cone {
+                    base,
+                    base_radius,
+                    top,
+                    top_radius,
+                    capped: props.contains_key("capped"),
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            "torus" => {
+                let props = self.properties(
+                    args,
+                    &[
+                        ("center", 3),
+                        ("axis", 3),
+                        ("major_radius", 1),
+                        ("minor_radius", 1),
+                        ("material", 1),
+                    ],
+                    &["center", "axis", "major_radius", "minor_radius", "material"],
+                )?;
+                let axis = self.vector(&props, "axis")?.unwrap();
+                let major_radius = self.number(&props, "major_radius")?.unwrap();
+                let minor_radius = self.number(&props, "minor_radius")?.unwrap();
+                if axis.length() == 0.0 {
+                    return Err(self.error("the axis of a torus can't be zero"));
+                }
+                if minor_radius <= 0.0 || minor_radius >= major_radius {
+                    return Err(self.error(
+                        "the minor radius of a torus has to be positive, and less than the major radius",
+                    ));
+                }
+                self.shapes.push(ShapeDescription::Torus {
+                    center: self.vector(&props, "center")?.unwrap(),
+                    axis,
+                    major_radius,
+                    minor_radius,
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            "mesh" => {
+                let props =
+                    self.properties(args, &[("path", 1), ("material", 1)], &["path", "material"])?;
+                self.shapes.push(ShapeDescription::Mesh {
+                    path: self.dir.join(&props["path"][0]),
+                    material: self.material_name(&props)?,
+                });
+                Ok(())
+            }
+            _ => Err(self.error(format!("unknown statement `{keyword}`"))),
+        }
+    }
+
+    fn camera(&mut self, args: &[String]) -> Result<(), DescriptionError> {
+        let props = self.properties(
+            args,
+            &[
+                ("look_from", 3),
+                ("look_at", 3),
+                ("roll", 1),
+                ("fov", 1),
+                ("aperture", 1),
+                ("focus_dist", 1),
+            ],
+            &[],
+        )?;
+
+        // later statements only override the properties they give
+        if let Some(look_from) = self.vector(&props, "look_from")? {
+            self.look_from = Some(look_from);
+        }
+        if let Some(look_at) = self.vector(&props, "look_at")? {
+            self.look_at = Some(look_at);
+        }
+        if let Some(roll) = self.number(&props, "roll")? {
+            self.camera.roll = roll;
+        }
+        if let Some(fov) = self.number(&props, "fov")? {
+            self.camera.fov = fov;
+        }
+        if let Some(aperture) = self.number(&props, "aperture")? {
+            self.camera.aperture = aperture;
+        }
+        if let Some(focus_dist) = self.number(&props, "focus_dist")? {
+            self.camera.focus_dist = Some(focus_dist);
+        }
+
+        Ok(())
+    }
+
+    fn render(&mut self, args: &[String]) -> Result<(), DescriptionError> {
+        let props = self.properties(
+            args,
+            &[
+                ("width", 1),
+                ("height", 1),
+                ("samples", 1),
+                ("max_depth", 1),
+                ("seed", 1),
+            ],
+            &[],
+        )?;
+
+        if let Some(width) = self.integer(&props, "width")? {
+            self.render.width = width as usize;
+        }
+        if let Some(height) = self.integer(&props, "height")? {
+            self.render.height = height as usize;
+        }
+        if let Some(samples) = self.integer(&props, "samples")? {
+            self.render.samples_per_pixel = samples;
+        }
+        if let Some(max_depth) = self.integer(&props, "max_depth")? {
+            self.render.max_depth = max_depth;
+        }
+        if let Some(seed) = self.integer(&props, "seed")? {
+            self.render.seed = Some(seed);
+        }
+
+        if self.render.width == 0 || self.render.height == 0 {
+            return Err(self.error("the image can't be empty"));
+        }
+        if self.render.samples_per_pixel == 0 {
+            return Err(self.error("each pixel needs at least one sample"));
+        }
+        Ok(())
+    }
+
+    fn background(&mut self, args: &[String]) -> Result<(), DescriptionError> {
+        let (kind, values) = match args.split_first() {
+            Some((kind, values)) => (kind.as_str(), values),
+            None => return Err(self.error("expected a kind of background")),
+        };
+
+        self.background = match kind {
+            "sky" => {
+                self.numbers(values, 0)?;
+                BackgroundDescription::Sky
+            }
+            "color" => {
+                let c = self.numbers(values, 3)?;
+                BackgroundDescription::Color(Color::new(c[0], c[1], c[2]))
+            }
+            "gradient" => {
+                let c = self.numbers(values, 6)?;
+                BackgroundDescription::Gradient {
+                    bottom: Color::new(c[0], c[1], c[2]),
+                    top: Color::new(c[3], c[4], c[5]),
+                }
+            }
+            "map" => match values {
+                [path] => BackgroundDescription::Map(self.dir.join(path)),
+                _ => return Err(self.error("expected the path of an image")),
+            },
+            _ => return Err(self.error(format!("unknown background `{kind}`"))),
+        };
+
+        Ok(())
+    }
+
+    fn material(&mut self, args: &[String]) -> Result<(), DescriptionError> {
+        let (name, kind, args) = match args {
+            [name, kind, args @ ..] => (name, kind.as_str(), args),
+            _ => return Err(self.error("expected a name and a kind of material")),
+        };
+        if self.materials.iter().any(|(n, _)| n == name) {
+            return Err(self.error(format!("material `{name}` is already defined")));
+        }
+
+        let material = match kind {
+            "lambertian" => {
+                let props = self.properties(args, &[("albedo", 3)], &["albedo"])?;
+                MaterialDescription::Lambertian {
+                    albedo: self.vector(&props, "albedo")?.unwrap(),
+                }
+            }
+            "metal" => {
+                let props = self.properties(args, &[("albedo", 3), ("fuzz", 1)], &["albedo"])?;
+                MaterialDescription::Metal {
+                    albedo: self.vector(&props, "albedo")?.unwrap(),
+                    fuzz: self.number(&props, "fuzz")?.unwrap_or(0.0),
+                }
+            }
+            "dielectric" => {
+                let props = self.properties(args, &[("ior", 1)], &["ior"])?;
+                MaterialDescription::Dielectric {
+                    index_of_refraction: self.number(&props, "ior")?.unwrap(),
+                }
+            }
+            "light" => {
+                let props = self.properties(args, &[("emit", 3)], &["emit"])?;
+                MaterialDescription::Light {
+                    emit: self.vector(&props, "emit")?.unwrap(),
+                }
+            }
+            _ => return Err(self.error(format!("unknown kind of material `{kind}`"))),
+        };
+
+        self.materials.push((name.clone(), material));
+        Ok(())
+    }
+
+    /// Splits a line into tokens at whitespace, keeping quoted
+    /// tokens together and dropping any comment.
+    fn tokenize(&self, line: &str) -> Result<Vec<String>, DescriptionError> {
+        let mut tokens = Vec::new();
+        let mut chars = line.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c == '#' {
+                break;
+            } else if c == '"' {
+                chars.next();
+                let mut token = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => token.push(c),
+                        None => return Err(self.error("unterminated quote")),
+                    }
+                }
+                tokens.push(token);
+            } else {
+                let mut token = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '#' {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// Groups a statement's arguments into properties, given how many
+    /// values each allowed property takes, and which are required.
+    fn properties<'t>(
+        &self,
+        args: &'t [String],
+        allowed: &[(&'static str, usize)],
+        required: &[&str],
+    ) -> Result<Properties<'t>, DescriptionError> {
+        let mut props = Properties::new();
+        let mut rest = args;
+
+        while let Some((name, values)) = rest.split_first() {
+            let (name, count) = allowed
+                .iter()
+                .find(|(n, _)| n == name)
+                .copied()
+                .ok_or_else(|| self.error(format!("unknown property `{name}`")))?;
+
+            if values.len() < count {
+                return Err(self.error(format!(
+                    "`{name}` needs {count} values, found {}",
+                    values.len()
+                )));
+            }
+            if props.insert(name, &values[..count]).is_some() {
+                return Err(self.error(format!("`{name}` is given more than once")));
+            }
+            rest = &values[count..];
+        }
+
+        if let Some(missing) = required.iter().find(|name| !props.contains_key(*name)) {
+            return Err(self.error(format!("missing `{missing}`")));
+        }
+
+        Ok(props)
+    }
+
+    fn material_name(&self, props: &Properties) -> Result<String, DescriptionError> {
+        let name = &props["material"][0];
+        if !self.materials.iter().any(|(n, _)| n == name) {
+            return Err(self.error(format!("unknown material `{name}`")));
+        }
+
+        Ok(name.clone())
+    }
+
+    fn number(&self, props: &Properties, name: &str) -> Result<Option<f64>, DescriptionError> {
+        props
+            .get(name)
+            .map(|values| self.float(&values[0]))
+            .transpose()
+    }
+
+    fn integer(&self, props: &Properties, name: &str) -> Result<Option<u64>, DescriptionError> {
+        props
+            .get(name)
+            .map(|values| {
+                values[0]
+                    .parse::<u64>()
+                    .map_err(|_| self.error(format!("invalid whole number `{}`", values[0])))
+            })
+            .transpose()
+    }
+
+    fn vector(&self, props: &Properties, name: &str) -> Result<Option<Vec3>, DescriptionError> {
+        props
+            .get(name)
+            .map(|values| {
+                let v = self.numbers(values, 3)?;
+                Ok(Vec3::new(v[0], v[1], v[2]))
+            })
+            .transpose()
+    }
+
+    fn numbers(&self, args: &[String], count: usize) -> Result<Vec<f64>, DescriptionError> {
+        if args.len() != count {
+            return Err(self.error(format!("expected {count} numbers, found {}", args.len())));
+        }
+
+        args.iter().map(|arg| self.float(arg)).collect()
+    }
+
+    fn float(&self, arg: &str) -> Result<f64, DescriptionError> {
+        arg.parse::<f64>()
+            .map_err(|_| self.error(format!("invalid number `{arg}`")))
+    }
+
+    fn error(&self, message: impl Into<String>) -> DescriptionError {
+        DescriptionError::parse(self.line, message)
+    }
+}
@@ -0,0 +1,157 @@
+//! Represents flat, round shapes with a dynamic material:
+//! disks, and annuli, which are disks with a hole in the middle.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::hit::{self, Hit, HitRecord};
+use super::material::Scatter;
+use super::onb::Onb;
+use super::random;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+pub struct Annulus {
+    center: Point3,
+    /// Axes along the ring for its texture coordinates, with the normal as `w`.
+    axes: Onb,
+    inner_radius: f64,
+    outer_radius: f64,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Annulus {
+    /// Creates the ring around `center` between the two radii, whose
+    /// front face points along `normal`, or returns none if the normal
+    /// is zero or the inner radius isn't between 0 and the outer one.
+    pub fn new(
+        center: Point3,
+        normal: Vec3,
+        inner_radius: f64,
+        outer_radius: f64,
+        mat: Arc<dyn Scatter>,
+    ) -> Option<Self> {
+        if normal.length() == 0.0 || !(0.0 <= inner_radius && inner_radius < outer_radius) {
+            return None;
+        }
+
+        Some(Self {
+            center,
+            axes: Onb::new(normal),
+            inner_radius,
+            outer_radius,
+            mat,
+        })
+    }
+
+    pub(crate) fn area(&self) -> f64 {
+        PI * (self.outer_radius.powi(2) - self.inner_radius.powi(2))
+    }
+
+    /// Picks a point uniformly by area.
+    pub(crate) fn random_point(&self) -> Point3 {
+        let mut rng = random::thread_rng();
+        let (r1, r2): (f64, f64) = (rng.gen(), rng.gen());
+        let (inner, outer) = (self.inner_radius.powi(2), self.outer_radius.powi(2));
+        let radius = (inner + r1 * (outer - inner)).sqrt();
+        let phi = 2.0 * PI * r2;
+
+        let p = Vec3::new(radius * phi.cos(), radius * phi.sin(), 0.0);
+        self.center + self.axes.local(p)
+    }
+}
+
+impl Hit for Annulus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let normal = self.axes.w();
+        let denom = normal.dot(r.direction());
+        if denom.abs() < 1e-12 {
+            // the ray is parallel to the ring
+            return None;
+        }
+
+        let t = normal.dot(self.center - r.origin()) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = self.axes.to_local(r.at(t) - self.center);
+        let radius = p.x().hypot(p.y());
+        if radius < self.inner_radius || radius > self.outer_radius {
+            return None;
+        }
+
+        // `u` goes around the ring, and `v` across it from the inside out
+        let mut rec = HitRecord::new(r, t, normal, &*self.mat);
+        rec.u = p.y().atan2(p.x()).rem_euclid(2.0 * PI) / (2.0 * PI);
+        rec.v = (radius - self.inner_radius) / (self.outer_radius - self.inner_radius);
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(circle_bounds(self.center, self.axes.w(), self.outer_radius))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        hit::area_pdf_value(self, self.area(), origin, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.random_point() - origin
+    }
+}
+
+/// A ring without a hole.
+pub struct Disk {
+    ring: Annulus,
+}
+
+impl Disk {
+    /// Creates the disk around `center` whose front face points along
+    /// `normal`, or returns none if the normal or the radius is zero,
+    /// or the radius is negative.
+    pub fn new(center: Point3, normal: Vec3, radius: f64, mat: Arc<dyn Scatter>) -> Option<Self> {
+        Some(Self {
+            ring: Annulus::new(center, normal, 0.0, radius, mat)?,
+        })
+    }
+
+    pub(crate) fn area(&self) -> f64 {
+        self.ring.area()
+    }
+
+    /// Picks a point uniformly by area.
+    pub(crate) fn random_point(&self) -> Point3 {
+        self.ring.random_point()
+    }
+}
+
+impl Hit for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.ring.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.ring.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.ring.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.ring.random(origin)
+    }
+}
+
+/// The box around a circle with the given unit `normal`, which
+/// reaches as far along each axis as the circle leans toward it.
+pub(crate) fn circle_bounds(center: Point3, normal: Vec3, radius: f64) -> Aabb {
+    let reach = |n: f64| radius * (1.0 - n * n).max(0.0).sqrt();
+    let extent = Vec3::new(reach(normal.x()), reach(normal.y()), reach(normal.z()));
+    Aabb::new(center - extent, center + extent)
+}
@@ -0,0 +1,158 @@
+//! Provides piecewise-constant probability distributions,
+//! for picking samples in proportion to some function,
+//! like the brightness of an environment map's pixels.
+
+use rand::Rng;
+
+use super::random;
+
+/// A distribution over `[0, 1)`, split into equally-sized
+/// pieces that each have a constant density.
+#[derive(Clone, Debug)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    /// `cdf[i]` is the probability of a sample landing before piece `i`.
+    cdf: Vec<f64>,
+    /// The integral of `func` over `[0, 1)`.
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// Creates a distribution proportional to `func`, which must not be
+    /// negative. If it's zero everywhere, the distribution is uniform.
+    pub fn new(func: Vec<f64>) -> Self {
+        let n = func.len() as f64;
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.0);
+        for (i, f) in func.iter().enumerate() {
+            cdf.push(cdf[i] + f / n);
+        }
+
+        let integral = cdf[func.len()];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f64 / n
+            };
+        }
+
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Turns a uniform random number `u` into a sample, returning its
+    /// position in `[0, 1)`, its density, and the piece it landed in.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // the last piece whose cdf doesn't exceed u
+        let i = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .clamp(1, self.func.len())
+            - 1;
+
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let du = if width > 0.0 {
+            (u - self.cdf[i]) / width
+        } else {
+            0.0
+        };
+
+        let x = ((i as f64 + du) / self.func.len() as f64).min(1.0 - f64::EPSILON);
+        (x, self.density(i), i)
+    }
+
+    /// The density of a sample landing at `x`.
+    pub fn pdf(&self, x: f64) -> f64 {
+        let n = self.func.len();
+        let i = ((x * n as f64) as usize).min(n - 1);
+        self.density(i)
+    }
+
+    fn density(&self, i: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.func[i] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// A distribution over the unit square, split into a grid of
+/// pieces that each have a constant density. Samples pick
+/// a row first, and then a column within that row.
+#[derive(Clone, Debug)]
+pub struct Distribution2D {
+    /// The distribution within each row.
+    conditional: Vec<Distribution1D>,
+    /// The distribution of the rows themselves.
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// Creates a distribution proportional to `func`, given row by row.
+    ///
+    /// # Panics
+    /// If the size of `func` doesn't match the dimensions.
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+
+        let conditional: Vec<Distribution1D> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Picks a random point, returning its `(x, y)` position and density.
+    pub fn sample(&self) -> ((f64, f64), f64) {
+        let mut rng = random::thread_rng();
+
+        let (y, pdf_y, row) = self.marginal.sample(rng.gen());
+        let (x, pdf_x, _) = self.conditional[row].sample(rng.gen());
+
+        ((x, y), pdf_x * pdf_y)
+    }
+
+    /// The density of a sample landing at `(x, y)`.
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        let n = self.conditional.len();
+        let row = ((y * n as f64) as usize).min(n - 1);
+
+        self.conditional[row].pdf(x) * self.marginal.pdf(y)
+    }
+}
+
+/// Weighs a sample taken from one of two distributions, when both could
+/// have produced it, in proportion to how likely each was to. Weighed
+/// this way, samples from both can be added up without counting twice.
+pub fn balance_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    if pdf + other_pdf > 0.0 {
+        pdf / (pdf + other_pdf)
+    } else {
+        0.0
+    }
+}
+
+/// Like [`balance_heuristic`], but favoring whichever distribution was
+/// more likely even more, which usually cuts the noise further.
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 {
+        a / (a + b)
+    } else {
+        0.0
+    }
+}
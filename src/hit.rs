@@ -2,19 +2,56 @@
 //! that can be hit by a ray. Anything that can
 //! be drawn needs to implement this trait.
 
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
 use super::material::Scatter;
+use super::random;
 use super::ray::Ray;
-use super::vec::{Point3, Vec3};
+use super::vec::{Color, Point3, Vec3};
 
 pub struct HitRecord<'a> {
     pub p: Point3,
+    /// The geometric normal, always facing against the incoming ray.
     pub normal: Vec3,
+    /// The normal used for shading, which may be interpolated across
+    /// the surface. Faces the same side as `normal`.
+    pub shading_normal: Vec3,
     pub mat: &'a dyn Scatter,
     pub t: f64,
     pub front_face: bool,
+    /// Surface coordinates of the hit point, for mapping textures.
+    pub u: f64,
+    pub v: f64,
+    /// Weights of a triangle's three vertices at the hit point,
+    /// if the object hit was a triangle.
+    pub barycentric: Option<[f64; 3]>,
+    /// Color interpolated from the vertices, if the object
+    /// hit has per-vertex colors.
+    pub color: Option<Color>,
 }
 
 impl<'a> HitRecord<'a> {
+    pub fn new(r: &Ray, t: f64, outward_normal: Vec3, mat: &'a dyn Scatter) -> Self {
+        let mut rec = Self {
+            p: r.at(t),
+            normal: outward_normal,
+            shading_normal: outward_normal,
+            mat,
+            t,
+            front_face: false,
+            u: 0.0,
+            v: 0.0,
+            barycentric: None,
+            color: None,
+        };
+
+        rec.set_face_normal(r, outward_normal);
+        rec
+    }
+
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
         self.front_face = r.direction().dot(outward_normal) < 0.0;
         self.normal = if self.front_face {
@@ -22,17 +59,85 @@ impl<'a> HitRecord<'a> {
         } else {
             -outward_normal
         };
+        self.shading_normal = self.normal;
+    }
+
+    /// Overrides the shading normal with an outward-facing
+    /// one, flipping it to match the side that was hit.
+    pub fn set_shading_normal(&mut self, outward_normal: Vec3) {
+        self.shading_normal = if self.front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
     }
 }
 
 pub trait Hit: Send + Sync {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    /// Returns a box enclosing the whole object, or none if
+    /// the object is unbounded and can't be put in a box.
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// The probability density, over solid angle, with which `random`
+    /// picks `direction` when looking at the object from `origin`.
+    /// Zero for objects that can't be sampled, like most lights can.
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// Picks a direction from `origin` toward a random point on the
+    /// object, for sending rays straight at lights.
+    fn random(&self, _origin: Point3) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+}
+
+/// Shared objects can be hit like the objects themselves, which
+/// lets the same object be in both the world and the lights.
+impl<T: Hit + ?Sized> Hit for Arc<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        (**self).random(origin)
+    }
+}
+
+/// Boxed objects can be hit like the objects themselves,
+/// which lets a tree hold objects of different kinds.
+impl<T: Hit + ?Sized> Hit for Box<T> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        (**self).random(origin)
+    }
 }
 
 pub type World = Vec<Box<dyn Hit>>;
 
 impl Hit for World {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut temp_rec = None;
         let mut closest_so_far = t_max;
 
@@ -45,4 +150,59 @@ impl Hit for World {
 
         temp_rec
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut output_box = Aabb::empty();
+        for object in self {
+            output_box = output_box.surrounding(object.bounding_box()?);
+        }
+
+        Some(output_box)
+    }
+
+    /// The density of picking `direction` from any of the objects,
+    /// since `random` picks one of them uniformly at random.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let sum: f64 = self
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum();
+        sum / self.len() as f64
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        if self.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+
+        let i = random::thread_rng().gen_range(0..self.len());
+        self[i].random(origin)
+    }
+}
+
+/// The density over solid angle of picking `direction` when `random`
+/// picks a point uniformly by area on an object with the given area.
+/// A direction can cross a curved surface more than once, and
+/// picking any of the points it crosses could have led to it.
+pub(crate) fn area_pdf_value(object: &dyn Hit, area: f64, origin: Point3, direction: Vec3) -> f64 {
+    let r = Ray::new(origin, direction);
+    let mut sum = 0.0;
+    let mut t_min = 0.001;
+    while let Some(rec) = object.hit(&r, t_min, f64::INFINITY) {
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = direction.normalized().dot(rec.normal).abs();
+        sum += distance_squared / cosine;
+        // just past the point found, to find the next one
+        t_min = rec.t + 1e-9 * rec.t.max(1.0);
+    }
+
+    sum / area
 }
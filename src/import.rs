@@ -0,0 +1,167 @@
+//! Imports scenes made for other renderers, using one of the
+//! submodules. Unlike a scene description, an imported scene is
+//! built as it's read, since the formats move and share geometry
+//! in ways the description can't express. Its camera and render
+//! settings are still kept as descriptions, so they can be changed
+//! before the scene is built.
+
+pub mod gltf;
+pub mod pbrt;
+
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::PathBuf;
+
+use image::ImageError;
+
+use super::background::{Background, SolidColor};
+use super::bvh::{Bvh, BvhBuilder, SplitMethod};
+use super::description::{CameraDescription, RenderSettings};
+use super::hit::{Hit, World};
+use super::mesh::MeshError;
+use super::scene::Scene;
+use super::vec::{Color, Vec3};
+
+pub struct ImportedScene {
+    pub camera: CameraDescription,
+    pub render: RenderSettings,
+    pub world: World,
+    /// The objects in `world` that give off light.
+    pub lights: World,
+    pub background: Box<dyn Background>,
+    /// Describes the parts of the file that were
+    /// skipped, since they aren't supported.
+    pub warnings: Vec<String>,
+}
+
+impl ImportedScene {
+    /// Creates an empty scene against a black background.
+    pub fn new(camera: CameraDescription) -> Self {
+        Self {
+            camera,
+            render: RenderSettings::default(),
+            world: World::new(),
+            lights: World::new(),
+            background: Box::new(SolidColor::new(Color::new(0.0, 0.0, 0.0))),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Puts the objects in a bounding volume hierarchy,
+    /// and sets up the camera to fit the render settings.
+    pub fn build(self) -> Scene {
+        let world = build_bvh(self.world);
+
+        let camera = self.camera.build(self.render.aspect_ratio());
+        Scene::new(Box::new(world), camera)
+            .with_background(self.background)
+            .with_lights(self.lights)
+    }
+}
+
+/// Builds a tree over the objects in the scene, or in
+/// an object that gets placed in the scene many times.
+fn build_bvh<T: Hit>(objects: Vec<T>) -> Bvh<T> {
+    BvhBuilder::new()
+        .split_method(SplitMethod::Sah { bins: 16 })
+        .max_leaf_size(4)
+        .build(objects)
+}
+
+/// The camera roll, in degrees, that turns the camera's up direction
+/// toward `up`, for a camera looking in the direction opposite `w`.
+fn roll(w: Vec3, up: Vec3) -> f64 {
+    // the rolled up direction is in the xy plane, and has
+    // to lie in the plane through `up` and `w`
+    let n = up.cross(w);
+    let mut angle = if n.x().abs() < 1e-9 && n.y().abs() < 1e-9 {
+        (-up.x()).atan2(up.y())
+    } else {
+        n.y().atan2(n.x())
+    };
+
+    // and it has to be on the same side of `w` as `up`
+    let rolled_up = Vec3::new(-angle.sin(), angle.cos(), 0.0);
+    if rolled_up.cross(w).dot(n) < 0.0 {
+        angle += PI;
+    }
+    angle.to_degrees()
+}
+
+/// An error encountered while importing a scene.
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    /// The file is malformed, as found on the given (1-based) line.
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// The file is malformed somewhere that doesn't have a line number.
+    Format(String),
+    Gltf(::gltf::Error),
+    /// A mesh the scene refers to failed to load.
+    Mesh {
+        path: PathBuf,
+        error: MeshError,
+    },
+    /// An image the scene refers to, by name or index, failed to load.
+    Image {
+        name: String,
+        error: ImageError,
+    },
+}
+
+impl ImportError {
+    pub(crate) fn parse(path: impl Into<PathBuf>, line: usize, message: impl Into<String>) -> Self {
+        Self::Parse {
+            path: path.into(),
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "{e}"),
+            ImportError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", path.display()),
+            ImportError::Format(message) => write!(f, "{message}"),
+            ImportError::Gltf(e) => write!(f, "{e}"),
+            ImportError::Mesh { path, error } => write!(f, "in {}: {error}", path.display()),
+            ImportError::Image { name, error } => write!(f, "in image {name}: {error}"),
+        }
+    }
+}
+
+impl Error for ImportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImportError::Io(e) => Some(e),
+            ImportError::Parse { .. } | ImportError::Format(_) => None,
+            ImportError::Gltf(e) => Some(e),
+            ImportError::Mesh { error, .. } => Some(error),
+            ImportError::Image { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(e: io::Error) -> Self {
+        ImportError::Io(e)
+    }
+}
+
+impl From<::gltf::Error> for ImportError {
+    fn from(e: ::gltf::Error) -> Self {
+        ImportError::Gltf(e)
+    }
+}
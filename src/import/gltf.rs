@@ -0,0 +1,490 @@
+//! Imports scenes from glTF 2.0 files, either `.gltf` JSON with
+//! its buffers and images embedded or in files beside it, or the
+//! binary `.glb` form.
+//!
+//! The meshes of every node in the scene are imported, moved into
+//! place by the node hierarchy's transforms, along with the first
+//! camera found. Meshes that several nodes share are only built once,
+//! and placed as instances. Materials use the metallic-roughness model, with
+//! their base color, metallic-roughness and emissive textures. Glass
+//! made with the transmission extension becomes a dielectric.
+//!
+//! glTF has nothing like a background, so the scene is lit by the
+//! default sky, as well as by any emissive materials.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use gltf::buffer;
+use gltf::camera::Projection;
+use gltf::image;
+use gltf::mesh::Mode;
+use gltf::{Document, Gltf, Node, Primitive};
+
+use super::{build_bvh, roll, ImportError, ImportedScene};
+use crate::aabb::Aabb;
+use crate::background::Gradient;
+use crate::description::CameraDescription;
+use crate::hit::{Hit, World};
+use crate::instance::Instance;
+use crate::material::{Dielectric, MetallicRoughness, Scatter};
+use crate::mesh::{MeshTriangle, TriangleMesh};
+use crate::texture::ImageTexture;
+use crate::transform::{Matrix, Transform};
+use crate::vec::{Color, Point3, Vec3};
+
+/// The extensions that are imported, all of them partially.
+const SUPPORTED_EXTENSIONS: [&str; 3] = [
+    "KHR_materials_emissive_strength",
+    "KHR_materials_ior",
+    "KHR_materials_transmission",
+];
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<ImportedScene, ImportError> {
+    let path = path.as_ref();
+    let Gltf { document, blob } = Gltf::open(path)?;
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let mut importer = Importer::new(&document, blob, dir)?;
+    importer.import()?;
+    Ok(importer.scene)
+}
+
+/// A material, and whether it gives off light.
+type ImportedMaterial = (Arc<dyn Scatter>, bool);
+
+/// A mesh built in its own space, for placing as instances, along
+/// with those of its primitives that give off light.
+type SharedMesh = (Arc<dyn Hit>, Vec<Arc<TriangleMesh>>);
+
+struct Importer<'d> {
+    document: &'d Document,
+    dir: PathBuf,
+    buffers: Vec<Vec<u8>>,
+    /// Textures by image index, and whether they hold colors.
+    textures: HashMap<(usize, bool), Arc<ImageTexture>>,
+    /// Materials by index, with the default material under none.
+    materials: HashMap<Option<usize>, ImportedMaterial>,
+    /// How many nodes place each mesh.
+    mesh_uses: Vec<usize>,
+    /// Meshes by index, if they're placed more than once.
+    shared_meshes: HashMap<usize, SharedMesh>,
+    has_camera: bool,
+    scene: ImportedScene,
+}
+
+impl<'d> Importer<'d> {
+    fn new(
+        document: &'d Document,
+        blob: Option<Vec<u8>>,
+        dir: PathBuf,
+    ) -> Result<Self, ImportError> {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut mesh_uses = vec![0; document.meshes().len()];
+        for mesh in document.nodes().filter_map(|node| node.mesh()) {
+            mesh_uses[mesh.index()] += 1;
+        }
+
+        let mut importer = Self {
+            document,
+            dir,
+            buffers: Vec::new(),
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            mesh_uses,
+            shared_meshes: HashMap::new(),
+            has_camera: false,
+            scene: ImportedScene::new(CameraDescription::new(origin, origin)),
+        };
+        importer.scene.background = Box::new(Gradient::sky());
+
+        let mut blob = blob;
+        for buffer in document.buffers() {
+            let data = match buffer.source() {
+                buffer::Source::Bin => blob.take().ok_or_else(|| {
+                    ImportError::Format(String::from("the binary buffer is missing"))
+                })?,
+                buffer::Source::Uri(uri) => importer.read_uri(uri)?,
+            };
+            if data.len() < buffer.length() {
+                return Err(ImportError::Format(format!(
+                    "buffer {} has {} bytes, expected {}",
+                    buffer.index(),
+                    data.len(),
+                    buffer.length()
+                )));
+            }
+            importer.buffers.push(data);
+        }
+
+        Ok(importer)
+    }
+
+    fn import(&mut self) -> Result<(), ImportError> {
+        for extension in self.document.extensions_used() {
+            if !SUPPORTED_EXTENSIONS.contains(&extension) {
+                self.warn(format!("unsupported extension `{extension}`"));
+            }
+        }
+
+        let scene = self
+            .document
+            .default_scene()
+            .or_else(|| self.document.scenes().next())
+            .ok_or_else(|| ImportError::Format(String::from("the file has no scenes")))?;
+        for node in scene.nodes() {
+            self.node(&node, Matrix::IDENTITY)?;
+        }
+
+        if !self.has_camera {
+            self.warn("the file has no camera, so one was put in front of the scene");
+            self.frame_scene();
+        }
+        Ok(())
+    }
+
+    /// Imports a node and its children, given the
+    /// transformation from its parent into the scene.
+    fn node(&mut self, node: &Node, parent: Matrix) -> Result<(), ImportError> {
+        let to_scene = parent * matrix(node.transform().matrix());
+
+        if let Some(camera) = node.camera() {
+            if !self.has_camera {
+                self.camera(camera.projection(), to_scene);
+                self.has_camera = true;
+            }
+        }
+        if let Some(mesh) = node.mesh() {
+            if self.mesh_uses[mesh.index()] > 1 {
+                self.instance(&mesh, to_scene)?;
+            } else {
+                for primitive in mesh.primitives() {
+                    if let Some((object, emissive)) = self.primitive(&primitive, to_scene)? {
+                        if emissive {
+                            self.scene.lights.push(Box::new(object.clone()));
+                        }
+                        self.scene.world.push(Box::new(object));
+                    }
+                }
+            }
+        }
+
+        for child in node.children() {
+            self.node(&child, to_scene)?;
+        }
+        Ok(())
+    }
+
+    /// Sets up the camera, which looks down its node's -z axis with +y up.
+    fn camera(&mut self, projection: Projection, to_scene: Matrix) {
+        let look_from = to_scene.point(Point3::new(0.0, 0.0, 0.0));
+        let look_at = to_scene.point(Point3::new(0.0, 0.0, -1.0));
+        let up = to_scene.vector(Vec3::new(0.0, 1.0, 0.0));
+
+        let mut camera = CameraDescription::new(look_from, look_at);
+        camera.roll = roll(look_from - look_at, up);
+        match projection {
+            Projection::Perspective(perspective) => {
+                camera.fov = (perspective.yfov() as f64).to_degrees();
+                if let Some(aspect_ratio) = perspective.aspect_ratio() {
+                    let render = &mut self.scene.render;
+                    render.height = ((render.width as f64 / aspect_ratio as f64) as usize).max(1);
+                }
+            }
+            Projection::Orthographic(_) => {
+                self.warn("orthographic cameras aren't supported, using a perspective one");
+            }
+        }
+        self.scene.camera = camera;
+    }
+
+    /// Points the camera at the middle of everything
+    /// in the scene, from far enough back to see it all.
+    fn frame_scene(&mut self) {
+        let bounds = self
+            .scene
+            .world
+            .iter()
+            .filter_map(|object| object.bounding_box())
+            .reduce(Aabb::surrounding);
+        let (center, radius) = match bounds {
+            Some(bounds) => (bounds.centroid(), bounds.extent().length() / 2.0),
+            None => (Point3::new(0.0, 0.0, 0.0), 1.0),
+        };
+
+        let camera = &mut self.scene.camera;
+        let distance = radius / (camera.fov.to_radians() / 2.0).sin();
+        camera.look_at = center;
+        camera.look_from = center + Vec3::new(0.0, 0.0, distance);
+    }
+
+    /// Places a mesh that's shared by several nodes, building it the first time.
+    fn instance(&mut self, mesh: &gltf::Mesh, to_scene: Matrix) -> Result<(), ImportError> {
+        let transform = match Transform::new(to_scene) {
+            Some(transform) => transform,
+            None => {
+                self.warn("skipping a mesh whose transformation can't be inverted");
+                return Ok(());
+            }
+        };
+
+        if !self.shared_meshes.contains_key(&mesh.index()) {
+            let mut primitives = World::new();
+            let mut lights = Vec::new();
+            for primitive in mesh.primitives() {
+                if let Some((object, emissive)) = self.primitive(&primitive, Matrix::IDENTITY)? {
+                    if emissive {
+                        lights.push(object.clone());
+                    }
+                    primitives.push(Box::new(object));
+                }
+            }
+
+            let shared: SharedMesh = (Arc::new(build_bvh(primitives)), lights);
+            self.shared_meshes.insert(mesh.index(), shared);
+        }
+
+        let (object, lights) = &self.shared_meshes[&mesh.index()];
+        for light in lights {
+            let instance = Instance::new(light.clone(), transform);
+            self.scene.lights.push(Box::new(instance));
+        }
+        let instance = Instance::new(object.clone(), transform);
+        self.scene.world.push(Box::new(instance));
+        Ok(())
+    }
+
+    /// Builds one part of a mesh, which has a single material, returning
+    /// it along with whether it gives off light. Returns none if there's
+    /// nothing to build.
+    fn primitive(
+        &mut self,
+        primitive: &Primitive,
+        to_scene: Matrix,
+    ) -> Result<Option<(Arc<TriangleMesh>, bool)>, ImportError> {
+        if primitive.mode() != Mode::Triangles {
+            self.warn(format!(
+                "skipping a primitive of mode {:?}, only triangles are supported",
+                primitive.mode()
+            ));
+            return Ok(None);
+        }
+
+        let buffers = &self.buffers;
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+        let positions: Vec<Point3> = match reader.read_positions() {
+            Some(positions) => positions.map(|p| to_scene.point(vector(p))).collect(),
+            None => {
+                self.warn("skipping a primitive without any positions");
+                return Ok(None);
+            }
+        };
+
+        let to_normals = to_scene.inverse().unwrap_or(Matrix::IDENTITY).transpose();
+        let normals: Vec<Vec3> = reader.read_normals().map_or_else(Vec::new, |normals| {
+            normals
+                .map(|n| to_normals.vector(vector(n)).normalized())
+                .collect()
+        });
+        // glTF's texture coordinates start at the top of the image
+        let uvs: Vec<(f64, f64)> = reader.read_tex_coords(0).map_or_else(Vec::new, |uvs| {
+            uvs.into_f32()
+                .map(|[u, v]| (u as f64, 1.0 - v as f64))
+                .collect()
+        });
+        let colors: Vec<Color> = reader.read_colors(0).map_or_else(Vec::new, |colors| {
+            colors.into_rgb_f32().map(vector).collect()
+        });
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+
+        if let Some(&index) = indices.iter().find(|&&i| i >= positions.len()) {
+            return Err(ImportError::Format(format!(
+                "vertex index {index} is out of range, only {} vertices",
+                positions.len()
+            )));
+        }
+        if indices.len() < 3 {
+            return Ok(None);
+        }
+
+        // mirroring turns the triangles inside out, unless they're flipped back
+        let flip = to_scene.determinant() < 0.0;
+        let triangles = indices
+            .chunks_exact(3)
+            .map(|c| {
+                let corners = if flip {
+                    [c[0], c[2], c[1]]
+                } else {
+                    [c[0], c[1], c[2]]
+                };
+                MeshTriangle {
+                    positions: corners,
+                    normals: (normals.len() == positions.len()).then_some(corners),
+                    uvs: (uvs.len() == positions.len()).then_some(corners),
+                    group: 0,
+                    material: 0,
+                }
+            })
+            .collect();
+
+        let (mat, emissive) = self.material(&primitive.material())?;
+        let positions_len = positions.len();
+        let mut mesh = TriangleMesh::new(positions, normals, uvs, triangles, mat);
+        if colors.len() == positions_len {
+            mesh = mesh.with_colors(colors);
+        } else if !colors.is_empty() {
+            self.warn("skipping vertex colors that don't match the positions");
+        }
+
+        Ok(Some((Arc::new(mesh), emissive)))
+    }
+
+    fn material(&mut self, material: &gltf::Material) -> Result<ImportedMaterial, ImportError> {
+        if let Some(imported) = self.materials.get(&material.index()) {
+            return Ok(imported.clone());
+        }
+
+        let strength = material.emissive_strength().unwrap_or(1.0) as f64;
+        let emission = strength * vector(material.emissive_factor());
+        let emissive = emission.length_squared() > 0.0;
+
+        let transmission = material
+            .transmission()
+            .map_or(0.0, |t| t.transmission_factor());
+        let imported: ImportedMaterial = if transmission > 0.0 {
+            let ior = material.ior().unwrap_or(1.5) as f64;
+            (Arc::new(Dielectric::new(ior)), false)
+        } else {
+            let pbr = material.pbr_metallic_roughness();
+            let [r, g, b, _] = pbr.base_color_factor();
+            let base_color = Color::new(r as f64, g as f64, b as f64);
+            let mut mat = MetallicRoughness::new(
+                base_color,
+                pbr.metallic_factor() as f64,
+                pbr.roughness_factor() as f64,
+            )
+            .with_emission(emission);
+
+            if let Some(info) = pbr.base_color_texture() {
+                mat = mat.with_base_color_texture(self.texture(&info, true)?);
+            }
+            if let Some(info) = pbr.metallic_roughness_texture() {
+                mat = mat.with_metallic_roughness_texture(self.texture(&info, false)?);
+            }
+            if let Some(info) = material.emissive_texture() {
+                mat = mat.with_emissive_texture(self.texture(&info, true)?);
+            }
+            (Arc::new(mat), emissive)
+        };
+
+        self.materials.insert(material.index(), imported.clone());
+        Ok(imported)
+    }
+
+    /// Loads a texture's image, decoding colors from gamma 2
+    /// if `color` is set, or as they are otherwise.
+    fn texture(
+        &mut self,
+        info: &gltf::texture::Info,
+        color: bool,
+    ) -> Result<Arc<ImageTexture>, ImportError> {
+        if info.tex_coord() != 0 {
+            self.warn("only the first set of texture coordinates is supported");
+        }
+
+        let source = info.texture().source();
+        let key = (source.index(), color);
+        if let Some(texture) = self.textures.get(&key) {
+            return Ok(texture.clone());
+        }
+
+        let name = match (source.name(), source.source()) {
+            (Some(name), _) => name.to_string(),
+            (None, image::Source::Uri { uri, .. }) if !uri.starts_with("data:") => uri.to_string(),
+            (None, _) => source.index().to_string(),
+        };
+        let data: Cow<[u8]> = match source.source() {
+            image::Source::View { view, .. } => {
+                let buffer = &self.buffers[view.buffer().index()];
+                Cow::Borrowed(&buffer[view.offset()..view.offset() + view.length()])
+            }
+            image::Source::Uri { uri, .. } => Cow::Owned(self.read_uri(uri)?),
+        };
+        let image = ::image::load_from_memory(&data)
+            .map_err(|error| ImportError::Image { name, error })?
+            .to_rgb8();
+
+        let texture = Arc::new(if color {
+            ImageTexture::from_image(&image)
+        } else {
+            ImageTexture::from_linear_image(&image)
+        });
+        self.textures.insert(key, texture.clone());
+        Ok(texture)
+    }
+
+    /// Reads the data a URI points to, either embedded
+    /// in the URI itself, or in a file beside the scene's.
+    fn read_uri(&self, uri: &str) -> Result<Vec<u8>, ImportError> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            return match data.split_once(',') {
+                Some((header, encoded)) if header.ends_with(";base64") => BASE64
+                    .decode(encoded)
+                    .map_err(|e| ImportError::Format(format!("invalid embedded data: {e}"))),
+                _ => Err(ImportError::Format(String::from(
+                    "embedded data has to be base64 encoded",
+                ))),
+            };
+        }
+
+        let path = self.dir.join(percent_decode(uri));
+        fs::read(&path)
+            .map_err(|e| ImportError::Format(format!("couldn't read {}: {e}", path.display())))
+    }
+
+    fn warn(&mut self, message: impl Into<String>) {
+        self.scene.warnings.push(message.into());
+    }
+}
+
+/// Converts a glTF matrix, which is given column by column.
+fn matrix(columns: [[f32; 4]; 4]) -> Matrix {
+    Matrix::from_columns(columns.map(|column| column.map(f64::from)))
+}
+
+fn vector([x, y, z]: [f32; 3]) -> Vec3 {
+    Vec3::new(x as f64, y as f64, z as f64)
+}
+
+/// Turns escapes like `%20` in a URI back into the characters they stand for.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
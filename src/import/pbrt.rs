@@ -0,0 +1,897 @@
+//! Imports scenes in a subset of the pbrt-v3 file format.
+//!
+//! The supported directives are the transformations (`LookAt`,
+//! `Translate`, `Rotate`, `Scale`, `Transform` and `ConcatTransform`),
+//! `AttributeBegin`/`End` and `TransformBegin`/`End`, a perspective
+//! `Camera`, the `Film` resolution, the `Sampler`'s pixel samples, the
+//! `Integrator`'s maximum depth and `Include`. Shapes can be spheres,
+//! triangle meshes and PLY meshes, made of matte, metal, mirror or glass
+//! `Material`s (named or not), or of a diffuse `AreaLightSource`. An
+//! infinite `LightSource` becomes a plain background color. Objects
+//! defined with `ObjectBegin` and `ObjectEnd` are built only once, no
+//! matter how many times `ObjectInstance` places them.
+//!
+//! Anything else is skipped, with a warning. Materials and lights can
+//! only have plain RGB colors, and area lights shine from both sides.
+//!
+//! pbrt's coordinates are left-handed, so the scene is mirrored to keep
+//! the image the right way around.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{build_bvh, roll, ImportError, ImportedScene};
+use crate::background::SolidColor;
+use crate::description::CameraDescription;
+use crate::hit::{Hit, World};
+use crate::instance::Instance;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Metal, Scatter};
+use crate::mesh::{ply, MeshTriangle, TriangleMesh};
+use crate::sphere::Sphere;
+use crate::transform::{Matrix, Transform};
+use crate::vec::{Color, Point3, Vec3};
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<ImportedScene, ImportError> {
+    let mut parser = Parser::new();
+    parser.include(path.as_ref())?;
+    parser.finish()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// A directive's name.
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Open,
+    Close,
+}
+
+/// A parameter of a directive, like `"rgb Kd" [0.5 0.5 0.5]`.
+#[derive(Clone, Debug)]
+struct Param {
+    ty: String,
+    name: String,
+    numbers: Vec<f64>,
+    strings: Vec<String>,
+}
+
+/// The state that `AttributeBegin` and `AttributeEnd` save and restore.
+#[derive(Clone)]
+struct Attributes {
+    transform: Matrix,
+    reverse_orientation: bool,
+    material: Arc<dyn Scatter>,
+    /// The light given off by shapes, if they're area lights.
+    area_light: Option<Color>,
+}
+
+/// The camera's parameters, kept until the image's shape is known.
+struct CameraSettings {
+    /// The transformation from world space to camera space.
+    transform: Matrix,
+    fov: f64,
+    lens_radius: f64,
+    focal_distance: f64,
+}
+
+struct Parser {
+    /// The files being read, with the innermost include last.
+    files: Vec<PathBuf>,
+    /// The line currently being parsed.
+    line: usize,
+    attributes: Attributes,
+    attribute_stack: Vec<Attributes>,
+    transform_stack: Vec<Matrix>,
+    named_materials: HashMap<String, Arc<dyn Scatter>>,
+    camera: CameraSettings,
+    /// Turns pbrt's world space into the scene's, mirroring it if needed.
+    world_to_scene: Matrix,
+    /// The object being defined, by name, with its shapes so far.
+    object: Option<(String, World)>,
+    /// The objects that have been defined, for placing with `ObjectInstance`.
+    objects: HashMap<String, Arc<dyn Hit>>,
+    scene: ImportedScene,
+}
+
+impl Parser {
+    fn new() -> Self {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        Self {
+            files: Vec::new(),
+            line: 0,
+            attributes: Attributes {
+                transform: Matrix::IDENTITY,
+                reverse_orientation: false,
+                material: default_material(),
+                area_light: None,
+            },
+            attribute_stack: Vec::new(),
+            transform_stack: Vec::new(),
+            named_materials: HashMap::new(),
+            camera: CameraSettings {
+                transform: Matrix::IDENTITY,
+                fov: 90.0,
+                lens_radius: 0.0,
+                focal_distance: 1e6,
+            },
+            world_to_scene: mirror(),
+            object: None,
+            objects: HashMap::new(),
+            scene: ImportedScene::new(CameraDescription::new(origin, origin)),
+        }
+    }
+
+    fn finish(mut self) -> Result<ImportedScene, ImportError> {
+        let camera_to_world = self
+            .camera
+            .transform
+            .inverse()
+            .ok_or_else(|| self.error("the camera transformation can't be inverted"))?;
+        let camera_to_scene = self.world_to_scene * camera_to_world;
+
+        let look_from = camera_to_scene.point(Point3::new(0.0, 0.0, 0.0));
+        let look_at = camera_to_scene.point(Point3::new(0.0, 0.0, 1.0));
+        let up = camera_to_scene.vector(Vec3::new(0.0, 1.0, 0.0));
+
+        // pbrt's field of view is across the image's shorter side
+        let render = &self.scene.render;
+        let half_fov = (self.camera.fov.to_radians() / 2.0).tan();
+        let fov = if render.width < render.height {
+            2.0 * (half_fov / render.aspect_ratio()).atan().to_degrees()
+        } else {
+            self.camera.fov
+        };
+
+        let mut camera = CameraDescription::new(look_from, look_at);
+        camera.roll = roll(look_from - look_at, up);
+        camera.fov = fov;
+        camera.aperture = 2.0 * self.camera.lens_radius;
+        if self.camera.lens_radius > 0.0 {
+            camera.focus_dist = Some(self.camera.focal_distance);
+        }
+
+        self.scene.camera = camera;
+        Ok(self.scene)
+    }
+
+    /// Parses a file, as if it were written out in place.
+    fn include(&mut self, path: &Path) -> Result<(), ImportError> {
+        let text = fs::read_to_string(path).map_err(|e| match self.files.last() {
+            // say which file the missing one was included from
+            Some(_) => self.error(format!("couldn't read {}: {e}", path.display())),
+            None => ImportError::Io(e),
+        })?;
+
+        // a file that ends up including itself would never finish
+        let canonical = fs::canonicalize(path).ok();
+        if canonical.is_some()
+            && self
+                .files
+                .iter()
+                .any(|file| fs::canonicalize(file).ok() == canonical)
+        {
+            return Err(self.error(format!(
+                "{} is already being read, so including it again would never end",
+                path.display()
+            )));
+        }
+
+        let outer_line = self.line;
+        self.files.push(path.to_path_buf());
+        let tokens = self.tokenize(&text)?;
+        self.parse(&tokens)?;
+        self.files.pop();
+        self.line = outer_line;
+
+        Ok(())
+    }
+
+    fn parse(&mut self, tokens: &[(Token, usize)]) -> Result<(), ImportError> {
+        let mut rest = tokens;
+        while let Some(((token, line), after)) = rest.split_first() {
+            self.line = *line;
+            let name = match token {
+                Token::Ident(name) => name.as_str(),
+                _ => return Err(self.error("expected a directive")),
+            };
+
+            // everything up to the next directive is its arguments
+            let count = after
+                .iter()
+                .position(|(token, _)| matches!(token, Token::Ident(_)))
+                .unwrap_or(after.len());
+            let args: Vec<Token> = after[..count].iter().map(|(t, _)| t.clone()).collect();
+            rest = &after[count..];
+
+            self.directive(name, &args)?;
+        }
+
+        Ok(())
+    }
+
+    fn directive(&mut self, name: &str, args: &[Token]) -> Result<(), ImportError> {
+        match name {
+            "LookAt" => {
+                let v = self.numbers(args, 9)?;
+                let look_at = look_at(
+                    Point3::new(v[0], v[1], v[2]),
+                    Point3::new(v[3], v[4], v[5]),
+                    Vec3::new(v[6], v[7], v[8]),
+                )
+                .ok_or_else(|| self.error("the up vector is along the view direction"))?;
+                self.transform(look_at);
+            }
+            "Translate" => {
+                let v = self.numbers(args, 3)?;
+                self.transform(Matrix::translate(Vec3::new(v[0], v[1], v[2])));
+            }
+            "Scale" => {
+                let v = self.numbers(args, 3)?;
+                self.transform(Matrix::scale(v[0], v[1], v[2]));
+            }
+            "Rotate" => {
+                let v = self.numbers(args, 4)?;
+                self.transform(Matrix::rotate(v[0], Vec3::new(v[1], v[2], v[3])));
+            }
+            "Identity" => self.attributes.transform = Matrix::IDENTITY,
+            "Transform" => self.attributes.transform = self.matrix(args)?,
+            "ConcatTransform" => {
+                let matrix = self.matrix(args)?;
+                self.transform(matrix);
+            }
+            "ReverseOrientation" => {
+                self.attributes.reverse_orientation = !self.attributes.reverse_orientation;
+            }
+            "AttributeBegin" => self.attribute_stack.push(self.attributes.clone()),
+            "AttributeEnd" => {
+                self.attributes = self
+                    .attribute_stack
+                    .pop()
+                    .ok_or_else(|| self.error("`AttributeEnd` without `AttributeBegin`"))?;
+            }
+            "TransformBegin" => self.transform_stack.push(self.attributes.transform),
+            "TransformEnd" => {
+                self.attributes.transform = self
+                    .transform_stack
+                    .pop()
+                    .ok_or_else(|| self.error("`TransformEnd` without `TransformBegin`"))?;
+            }
+            "Camera" => {
+                let (kind, params) = self.typed_params(args)?;
+                if kind != "perspective" {
+                    self.warn(format!(
+                        "unsupported camera `{kind}`, using a perspective one"
+                    ));
+                }
+                self.camera = CameraSettings {
+                    transform: self.attributes.transform,
+                    fov: self.float(&params, "fov", 90.0)?,
+                    lens_radius: self.float(&params, "lensradius", 0.0)?,
+                    focal_distance: self.float(&params, "focaldistance", 1e6)?,
+                };
+            }
+            "Film" => {
+                let (_, params) = self.typed_params(args)?;
+                let width = self.float(&params, "xresolution", 1280.0)?;
+                let height = self.float(&params, "yresolution", 720.0)?;
+                if width < 1.0 || height < 1.0 {
+                    return Err(self.error("the image can't be empty"));
+                }
+                self.scene.render.width = width as usize;
+                self.scene.render.height = height as usize;
+            }
+            "Sampler" => {
+                let (_, params) = self.typed_params(args)?;
+                if let Some(samples) = self.optional_float(&params, "pixelsamples")? {
+                    if samples < 1.0 {
+                        return Err(self.error("it takes at least one sample per pixel"));
+                    }
+                    self.scene.render.samples_per_pixel = samples as u64;
+                }
+            }
+            "Integrator" => {
+                let (_, params) = self.typed_params(args)?;
+                if let Some(depth) = self.optional_float(&params, "maxdepth")? {
+                    self.scene.render.max_depth = depth as u64;
+                }
+            }
+            // these only change how pbrt renders the image, not what's in it
+            "PixelFilter" | "Accelerator" => {}
+            "WorldBegin" => {
+                self.attributes.transform = Matrix::IDENTITY;
+                // a camera that's already mirrored doesn't need the scene to be
+                self.world_to_scene = if self.camera.transform.determinant() < 0.0 {
+                    Matrix::IDENTITY
+                } else {
+                    mirror()
+                };
+            }
+            "WorldEnd" => {}
+            "Material" => {
+                let (kind, params) = self.typed_params(args)?;
+                self.attributes.material = self.material(&kind, &params)?;
+            }
+            "MakeNamedMaterial" => {
+                let (name, params) = self.typed_params(args)?;
+                let kind = match find(&params, "type") {
+                    Some(param) => self.string(param)?,
+                    None => return Err(self.error(format!("material `{name}` has no type"))),
+                };
+                let material = self.material(&kind, &params)?;
+                self.named_materials.insert(name, material);
+            }
+            "NamedMaterial" => {
+                let name = self.name(args)?;
+                self.attributes.material = self
+                    .named_materials
+                    .get(&name)
+                    .cloned()
+                    .ok_or_else(|| self.error(format!("unknown material `{name}`")))?;
+            }
+            "AreaLightSource" => {
+                let (kind, params) = self.typed_params(args)?;
+                if kind != "diffuse" {
+                    self.warn(format!(
+                        "unsupported area light `{kind}`, using a diffuse one"
+                    ));
+                }
+                let scale = self.color(&params, "scale", Color::new(1.0, 1.0, 1.0))?;
+                let emit = self.color(&params, "L", Color::new(1.0, 1.0, 1.0))?;
+                self.attributes.area_light = Some(scale * emit);
+            }
+            "LightSource" => {
+                let (kind, params) = self.typed_params(args)?;
+                if kind == "infinite" {
+                    if find(&params, "mapname").is_some() {
+                        self.warn("environment maps aren't supported, using a plain color");
+                    }
+                    let scale = self.color(&params, "scale", Color::new(1.0, 1.0, 1.0))?;
+                    let color = self.color(&params, "L", Color::new(1.0, 1.0, 1.0))?;
+                    self.scene.background = Box::new(SolidColor::new(scale * color));
+                } else {
+                    self.warn(format!("unsupported light `{kind}`"));
+                }
+            }
+            "Shape" => {
+                let (kind, params) = self.typed_params(args)?;
+                self.shape(&kind, &params)?;
+            }
+            "ObjectBegin" => {
+                if self.object.is_some() {
+                    return Err(self.error("objects can't be defined inside other objects"));
+                }
+                let name = self.name(args)?;
+                self.attribute_stack.push(self.attributes.clone());
+                self.object = Some((name, World::new()));
+            }
+            "ObjectEnd" => {
+                let (name, shapes) = self
+                    .object
+                    .take()
+                    .ok_or_else(|| self.error("`ObjectEnd` without `ObjectBegin`"))?;
+                self.attributes = self
+                    .attribute_stack
+                    .pop()
+                    .ok_or_else(|| self.error("`ObjectEnd` without `ObjectBegin`"))?;
+                self.objects.insert(name, Arc::new(build_bvh(shapes)));
+            }
+            "ObjectInstance" => {
+                if self.object.is_some() {
+                    return Err(self.error("objects can't be placed inside other objects"));
+                }
+                let name = self.name(args)?;
+                let object = match self.objects.get(&name) {
+                    Some(object) => object.clone(),
+                    None => return Err(self.error(format!("undefined object `{name}`"))),
+                };
+
+                match Transform::new(self.world_to_scene * self.attributes.transform) {
+                    Some(transform) => {
+                        let instance = Instance::new(object, transform);
+                        self.scene.world.push(Box::new(instance));
+                    }
+                    None => {
+                        self.warn("the instance's transformation can't be inverted, skipping it")
+                    }
+                }
+            }
+            "Include" | "Import" => {
+                let name = self.name(args)?;
+                let path = self.dir().join(name);
+                self.include(&path)?;
+            }
+            _ => self.warn(format!("unsupported directive `{name}`")),
+        }
+
+        Ok(())
+    }
+
+    /// Applies a transformation to everything after it, before the current one.
+    fn transform(&mut self, t: Matrix) {
+        self.attributes.transform = self.attributes.transform * t;
+    }
+
+    fn material(&mut self, kind: &str, params: &[Param]) -> Result<Arc<dyn Scatter>, ImportError> {
+        Ok(match kind {
+            "matte" => {
+                let albedo = self.color(params, "Kd", Color::new(0.5, 0.5, 0.5))?;
+                Arc::new(Lambertian::new(albedo))
+            }
+            "metal" => {
+                // copper, unless told otherwise
+                let eta = self.color(params, "eta", Color::new(0.2004, 0.9240, 1.1022))?;
+                let k = self.color(params, "k", Color::new(3.9129, 2.4528, 2.1421))?;
+                let roughness = self.float(params, "roughness", 0.01)?;
+                let u = self.float(params, "uroughness", roughness)?;
+                let v = self.float(params, "vroughness", roughness)?;
+
+                let albedo = Color::new(
+                    reflectance(eta.x(), k.x()),
+                    reflectance(eta.y(), k.y()),
+                    reflectance(eta.z(), k.z()),
+                );
+                Arc::new(Metal::new(albedo, (u + v) / 2.0))
+            }
+            "mirror" => {
+                let albedo = self.color(params, "Kr", Color::new(0.9, 0.9, 0.9))?;
+                Arc::new(Metal::new(albedo, 0.0))
+            }
+            "glass" => {
+                let index = self.float(params, "index", 1.5)?;
+                let index = self.float(params, "eta", index)?;
+                Arc::new(Dielectric::new(index))
+            }
+            _ => {
+                self.warn(format!("unsupported material `{kind}`, using a matte one"));
+                let albedo = self.color(params, "Kd", Color::new(0.5, 0.5, 0.5))?;
+                Arc::new(Lambertian::new(albedo))
+            }
+        })
+    }
+
+    fn shape(&mut self, kind: &str, params: &[Param]) -> Result<(), ImportError> {
+        // the shapes of an object stay in the world space it was defined
+        // in, and each instance moves them into the scene on its own
+        let to_scene = match self.object {
+            Some(_) => self.attributes.transform,
+            None => self.world_to_scene * self.attributes.transform,
+        };
+        let mat: Arc<dyn Scatter> = match self.attributes.area_light {
+            Some(emit) => Arc::new(DiffuseLight::new(emit)),
+            None => self.attributes.material.clone(),
+        };
+
+        let object: Arc<dyn Hit> = match kind {
+            "sphere" => {
+                if ["zmin", "zmax", "phimax"]
+                    .iter()
+                    .any(|name| find(params, name).is_some())
+                {
+                    self.warn("partial spheres aren't supported, using a whole one");
+                }
+
+                let scales = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)]
+                    .map(|axis| to_scene.vector(axis).length());
+                if (scales[0] - scales[1]).abs() > 1e-6 * scales[0] {
+                    self.warn("spheres can't be stretched, scaling it evenly instead");
+                }
+
+                let radius = self.float(params, "radius", 1.0)?;
+                let center = to_scene.point(Point3::new(0.0, 0.0, 0.0));
+                let scale = to_scene.determinant().abs().cbrt();
+                Arc::new(Sphere::new(center, radius * scale, mat))
+            }
+            "trianglemesh" => Arc::new(self.triangle_mesh(params, to_scene, mat)?),
+            "plymesh" => {
+                let name = match find(params, "filename") {
+                    Some(param) => self.string(param)?,
+                    None => return Err(self.error("a PLY mesh needs a `filename`")),
+                };
+                let path = self.dir().join(name);
+                let mesh =
+                    ply::load(&path, mat).map_err(|error| ImportError::Mesh { path, error })?;
+
+                let normals = to_normals(to_scene);
+                let flip = self.flips(to_scene);
+                Arc::new(mesh.map_vertices(
+                    |p| to_scene.point(p),
+                    |n| normals.vector(n).normalized(),
+                    flip,
+                ))
+            }
+            _ => {
+                self.warn(format!("unsupported shape `{kind}`"));
+                return Ok(());
+            }
+        };
+
+        if let Some((_, shapes)) = &mut self.object {
+            shapes.push(Box::new(object));
+            if self.attributes.area_light.is_some() {
+                self.warn("area lights in objects aren't sampled directly, so they'll be noisy");
+            }
+            return Ok(());
+        }
+
+        if self.attributes.area_light.is_some() {
+            self.scene.lights.push(Box::new(object.clone()));
+        }
+        self.scene.world.push(Box::new(object));
+        Ok(())
+    }
+
+    fn triangle_mesh(
+        &self,
+        params: &[Param],
+        to_scene: Matrix,
+        mat: Arc<dyn Scatter>,
+    ) -> Result<TriangleMesh, ImportError> {
+        let positions: Vec<Point3> = match find(params, "P") {
+            Some(param) => self.vectors(param)?,
+            None => return Err(self.error("a triangle mesh needs its points, `P`")),
+        };
+        let indices: Vec<usize> = match find(params, "indices") {
+            Some(param) => {
+                if let Some(&index) = param.numbers.iter().find(|&&i| i < 0.0) {
+                    return Err(self.error(format!("vertex index {index} is negative")));
+                }
+                param.numbers.iter().map(|&i| i as usize).collect()
+            }
+            // a single triangle can leave them out
+            None if positions.len() == 3 => vec![0, 1, 2],
+            None => return Err(self.error("a triangle mesh needs its `indices`")),
+        };
+        if !indices.len().is_multiple_of(3) {
+            return Err(self.error("the number of indices isn't a multiple of 3"));
+        }
+        if let Some(&index) = indices.iter().find(|&&i| i >= positions.len()) {
+            return Err(self.error(format!(
+                "vertex index {index} is out of range, only {} vertices",
+                positions.len()
+            )));
+        }
+
+        let normals: Vec<Vec3> = match find(params, "N") {
+            Some(param) => self.vectors(param)?,
+            None => Vec::new(),
+        };
+        let uvs: Vec<(f64, f64)> = match find(params, "uv").or_else(|| find(params, "st")) {
+            Some(param) => {
+                if !param.numbers.len().is_multiple_of(2) {
+                    return Err(self.error(format!("`{}` needs two numbers per point", param.name)));
+                }
+                param
+                    .numbers
+                    .chunks_exact(2)
+                    .map(|uv| (uv[0], uv[1]))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(self.error("there has to be one normal per point"));
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return Err(self.error("there have to be one pair of texture coordinates per point"));
+        }
+
+        let flip = self.flips(to_scene);
+        let triangles = indices
+            .chunks(3)
+            .map(|c| {
+                let corners = if flip {
+                    [c[0], c[2], c[1]]
+                } else {
+                    [c[0], c[1], c[2]]
+                };
+                MeshTriangle {
+                    positions: corners,
+                    normals: (!normals.is_empty()).then_some(corners),
+                    uvs: (!uvs.is_empty()).then_some(corners),
+                    group: 0,
+                    material: 0,
+                }
+            })
+            .collect();
+
+        let to_normals = to_normals(to_scene);
+        let positions = positions.into_iter().map(|p| to_scene.point(p)).collect();
+        let normals = normals
+            .into_iter()
+            .map(|n| to_normals.vector(n).normalized())
+            .collect();
+        Ok(TriangleMesh::new(positions, normals, uvs, triangles, mat))
+    }
+
+    /// Whether triangles have to be turned around to keep facing the
+    /// way they did, after the transformation into the scene.
+    fn flips(&self, to_scene: Matrix) -> bool {
+        (to_scene.determinant() < 0.0) != self.attributes.reverse_orientation
+    }
+
+    /// Splits a directive's arguments into its type, or name, and its parameters.
+    fn typed_params(&self, args: &[Token]) -> Result<(String, Vec<Param>), ImportError> {
+        match args.split_first() {
+            Some((Token::Str(kind), rest)) => Ok((kind.clone(), self.params(rest)?)),
+            _ => Err(self.error("expected a quoted type")),
+        }
+    }
+
+    fn params(&self, args: &[Token]) -> Result<Vec<Param>, ImportError> {
+        let mut params = Vec::new();
+        let mut rest = args;
+
+        while let Some((token, after)) = rest.split_first() {
+            let declaration = match token {
+                Token::Str(declaration) => declaration,
+                _ => return Err(self.error("expected a quoted parameter declaration")),
+            };
+            let (ty, name) = match declaration.split_whitespace().collect::<Vec<_>>()[..] {
+                [ty, name] => (ty.to_string(), name.to_string()),
+                _ => {
+                    return Err(self.error(format!(
+                        "expected a parameter's type and name, found `{declaration}`"
+                    )))
+                }
+            };
+
+            // the values are either a single one, or a list in brackets
+            let values = match after.first() {
+                Some(Token::Open) => {
+                    let end = after
+                        .iter()
+                        .position(|t| *t == Token::Close)
+                        .ok_or_else(|| self.error("unclosed `[`"))?;
+                    rest = &after[end + 1..];
+                    &after[1..end]
+                }
+                Some(Token::Num(_) | Token::Str(_)) => {
+                    rest = &after[1..];
+                    &after[..1]
+                }
+                _ => return Err(self.error(format!("`{name}` has no value"))),
+            };
+
+            let mut param = Param {
+                ty,
+                name,
+                numbers: Vec::new(),
+                strings: Vec::new(),
+            };
+            for value in values {
+                match value {
+                    Token::Num(n) => param.numbers.push(*n),
+                    Token::Str(s) => param.strings.push(s.clone()),
+                    _ => return Err(self.error(format!("invalid value for `{}`", param.name))),
+                }
+            }
+            params.push(param);
+        }
+
+        Ok(params)
+    }
+
+    fn name(&self, args: &[Token]) -> Result<String, ImportError> {
+        match args {
+            [Token::Str(name)] => Ok(name.clone()),
+            _ => Err(self.error("expected a quoted name")),
+        }
+    }
+
+    fn string(&self, param: &Param) -> Result<String, ImportError> {
+        match &param.strings[..] {
+            [value] => Ok(value.clone()),
+            _ => Err(self.error(format!("`{}` needs a single string", param.name))),
+        }
+    }
+
+    fn optional_float(&self, params: &[Param], name: &str) -> Result<Option<f64>, ImportError> {
+        find(params, name)
+            .map(|param| match param.numbers[..] {
+                [value] => Ok(value),
+                _ => Err(self.error(format!("`{name}` needs a single number"))),
+            })
+            .transpose()
+    }
+
+    fn float(&self, params: &[Param], name: &str, default: f64) -> Result<f64, ImportError> {
+        Ok(self.optional_float(params, name)?.unwrap_or(default))
+    }
+
+    /// Reads a color, or a single number to use for all three
+    /// channels. Colors that aren't RGB are skipped with a warning.
+    fn color(
+        &mut self,
+        params: &[Param],
+        name: &str,
+        default: Color,
+    ) -> Result<Color, ImportError> {
+        let param = match find(params, name) {
+            Some(param) => param,
+            None => return Ok(default),
+        };
+
+        match (param.ty.as_str(), &param.numbers[..]) {
+            ("rgb" | "color", [r, g, b]) => Ok(Color::new(*r, *g, *b)),
+            ("float", [v]) => Ok(Color::new(*v, *v, *v)),
+            ("rgb" | "color" | "float", _) => {
+                Err(self.error(format!("`{name}` has the wrong number of values")))
+            }
+            (ty, _) => {
+                self.warn(format!("`{ty} {name}` isn't supported, using the default"));
+                Ok(default)
+            }
+        }
+    }
+
+    fn vectors(&self, param: &Param) -> Result<Vec<Vec3>, ImportError> {
+        if !param.numbers.len().is_multiple_of(3) {
+            return Err(self.error(format!("`{}` needs three numbers per point", param.name)));
+        }
+
+        Ok(param
+            .numbers
+            .chunks(3)
+            .map(|v| Vec3::new(v[0], v[1], v[2]))
+            .collect())
+    }
+
+    /// Reads the 16 numbers of a matrix, which pbrt lists column by column.
+    fn matrix(&self, args: &[Token]) -> Result<Matrix, ImportError> {
+        let args = match args {
+            [Token::Open, inner @ .., Token::Close] => inner,
+            _ => args,
+        };
+        let v = self.numbers(args, 16)?;
+
+        let mut columns = [[0.0; 4]; 4];
+        for (j, column) in columns.iter_mut().enumerate() {
+            column.copy_from_slice(&v[j * 4..j * 4 + 4]);
+        }
+        Ok(Matrix::from_columns(columns))
+    }
+
+    fn numbers(&self, args: &[Token], count: usize) -> Result<Vec<f64>, ImportError> {
+        let numbers: Vec<f64> = args
+            .iter()
+            .filter_map(|t| match t {
+                Token::Num(n) => Some(*n),
+                _ => None,
+            })
+            .collect();
+        if numbers.len() != count || args.len() != count {
+            return Err(self.error(format!("expected {count} numbers")));
+        }
+
+        Ok(numbers)
+    }
+
+    /// Splits a file into tokens, each with the line it's on.
+    fn tokenize(&mut self, text: &str) -> Result<Vec<(Token, usize)>, ImportError> {
+        let mut tokens = Vec::new();
+        let mut chars = text.chars().peekable();
+        self.line = 1;
+
+        while let Some(&c) = chars.peek() {
+            let line = self.line;
+            match c {
+                '\n' => {
+                    self.line += 1;
+                    chars.next();
+                }
+                _ if c.is_whitespace() => {
+                    chars.next();
+                }
+                '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+                '[' | ']' => {
+                    chars.next();
+                    let token = if c == '[' { Token::Open } else { Token::Close };
+                    tokens.push((token, line));
+                }
+                '"' => {
+                    chars.next();
+                    let mut s = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\n') | None => return Err(self.error("unterminated quote")),
+                            Some(c) => s.push(c),
+                        }
+                    }
+                    tokens.push((Token::Str(s), line));
+                }
+                _ => {
+                    let mut word = String::new();
+                    while let Some(c) = chars
+                        .next_if(|&c| !c.is_whitespace() && !matches!(c, '"' | '[' | ']' | '#'))
+                    {
+                        word.push(c);
+                    }
+
+                    let token = if word.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                        Token::Ident(word)
+                    } else {
+                        let n = word
+                            .parse()
+                            .map_err(|_| self.error(format!("invalid number `{word}`")))?;
+                        Token::Num(n)
+                    };
+                    tokens.push((token, line));
+                }
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// The directory that paths in the current file are relative to.
+    fn dir(&self) -> PathBuf {
+        self.files
+            .last()
+            .and_then(|path| path.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    }
+
+    fn warn(&mut self, message: impl Into<String>) {
+        let path = self.files.last().cloned().unwrap_or_default();
+        let warning = format!("{}:{}: {}", path.display(), self.line, message.into());
+        self.scene.warnings.push(warning);
+    }
+
+    fn error(&self, message: impl Into<String>) -> ImportError {
+        let path = self.files.last().cloned().unwrap_or_default();
+        ImportError::parse(path, self.line, message)
+    }
+}
+
+/// The material shapes have before any other is set.
+fn default_material() -> Arc<dyn Scatter> {
+    Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+}
+
+fn find<'p>(params: &'p [Param], name: &str) -> Option<&'p Param> {
+    params.iter().find(|param| param.name == name)
+}
+
+/// Mirrors across the plane x = 0, which turns pbrt's
+/// left-handed coordinates into right-handed ones.
+fn mirror() -> Matrix {
+    Matrix::scale(-1.0, 1.0, 1.0)
+}
+
+/// The transformation from world space into the space of a camera at
+/// `eye` looking toward `target`, the way pbrt sets it up.
+fn look_at(eye: Point3, target: Point3, up: Vec3) -> Option<Matrix> {
+    let dir = (target - eye).normalized();
+    let right = up.normalized().cross(dir);
+    if right.length() < 1e-9 {
+        return None;
+    }
+    let right = right.normalized();
+    let new_up = dir.cross(right);
+
+    let column = |v: Vec3, w: f64| [v.x(), v.y(), v.z(), w];
+    let camera_to_world = Matrix::from_columns([
+        column(right, 0.0),
+        column(new_up, 0.0),
+        column(dir, 0.0),
+        column(eye, 1.0),
+    ]);
+    camera_to_world.inverse()
+}
+
+/// The transformation for normals, which have to
+/// stay perpendicular to the surfaces they belong to.
+fn to_normals(t: Matrix) -> Matrix {
+    t.inverse().unwrap_or(Matrix::IDENTITY).transpose()
+}
+
+/// The fraction of light a metal reflects straight back, from
+/// its index of refraction `eta` and absorption `k`.
+fn reflectance(eta: f64, k: f64) -> f64 {
+    ((eta - 1.0).powi(2) + k * k) / ((eta + 1.0).powi(2) + k * k)
+}
@@ -0,0 +1,70 @@
+//! Provides instances, which place a copy of an object somewhere
+//! else in the scene. The object itself is shared, so a detailed
+//! mesh can be put in many places while it's stored only once.
+
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::ray::Ray;
+use super::transform::Transform;
+use super::vec::{Point3, Vec3};
+
+pub struct Instance {
+    object: Arc<dyn Hit>,
+    /// The transformation from the object's own space into the scene.
+    transform: Transform,
+    bounds: Option<Aabb>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hit>, transform: Transform) -> Self {
+        let bounds = object.bounding_box().map(|b| transform.bounding_box(b));
+        Self {
+            object,
+            transform,
+            bounds,
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+}
+
+impl Hit for Instance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // the transformed ray reaches the same points at the
+        // same times, so the interval doesn't have to change
+        let local = self.transform.inverse().ray(r);
+        let mut rec = self.object.hit(&local, t_min, t_max)?;
+
+        // normals keep facing against the ray, since the
+        // transformation doesn't change which side is which
+        rec.p = self.transform.point(rec.p);
+        rec.normal = self.transform.normal(rec.normal).normalized();
+        rec.shading_normal = self.transform.normal(rec.shading_normal).normalized();
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let to_object = self.transform.inverse();
+        let local = to_object.vector(direction.normalized());
+        let pdf = self.object.pdf_value(to_object.point(origin), local);
+
+        // the transformation can stretch the directions around
+        // `origin` apart, which spreads the density more thinly
+        pdf * to_object.matrix().determinant().abs() / local.length().powi(3)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let to_object = self.transform.inverse();
+        self.transform
+            .vector(self.object.random(to_object.point(origin)))
+    }
+}
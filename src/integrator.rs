@@ -0,0 +1,300 @@
+//! Provides integrators, which work out how much light travels
+//! back along a ray from the scene. Different integrators use
+//! different algorithms to follow the light around.
+//!
+//! The path tracers here sample the lights and background directly
+//! at every non-specular hit, and use multiple importance sampling
+//! to combine that with the light bounced rays find on their own.
+
+use rand::Rng;
+
+use super::distribution::power_heuristic;
+use super::hit::{Hit, HitRecord};
+use super::material::Lobes;
+use super::random;
+use super::ray::Ray;
+use super::scene::Scene;
+use super::vec::{Color, Point3};
+
+pub trait Integrator: Send + Sync {
+    /// The light arriving along `r`, following it for
+    /// at most `depth` bounces around the scene.
+    fn ray_color(&self, r: &Ray, scene: &Scene, depth: u64) -> Color;
+}
+
+/// The number of bounces after which paths start being
+/// randomly terminated, unless set otherwise.
+const DEFAULT_ROULETTE_DEPTH: u64 = 3;
+
+/// A path tracer that recurses once per bounce. It's the most direct way
+/// to write the algorithm, but deep paths can run out of stack.
+///
+/// After a few bounces, paths are ended at random with Russian roulette,
+/// more likely the less light still gets through them, and the ones that
+/// survive are made brighter to make up for it. This keeps the result
+/// unbiased while rarely tracing paths all the way to the depth limit.
+#[derive(Clone, Copy, Debug)]
+pub struct SimplePathIntegrator {
+    roulette_depth: u64,
+}
+
+impl SimplePathIntegrator {
+    pub fn new() -> Self {
+        Self {
+            roulette_depth: DEFAULT_ROULETTE_DEPTH,
+        }
+    }
+
+    /// Sets the number of bounces after which Russian roulette starts.
+    /// Set it to the depth limit to never end paths early.
+    pub fn with_roulette_depth(mut self, roulette_depth: u64) -> Self {
+        self.roulette_depth = roulette_depth;
+        self
+    }
+
+    /// Traces the path after `bounces` bounces, which only gets to
+    /// bounce `depth` times in total, and lets through `throughput`
+    /// of the light it finds.
+    fn trace(
+        &self,
+        r: &Ray,
+        scene: &Scene,
+        depth: u64,
+        bounces: u64,
+        throughput: Color,
+        bounce: Option<Bounce>,
+    ) -> Color {
+        if bounces >= depth {
+            // if we've exceeded the allowed number of ray bounces, stop gathering more info
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let rec = match scene.world().hit(r, 0.001, f64::INFINITY) {
+            Some(rec) => rec,
+            None => return background_light(r, scene, bounce),
+        };
+
+        let emitted = emitted_light(r, &rec, scene, bounce);
+        let sample = match rec.mat.sample(r, &rec) {
+            Some(sample) => sample,
+            None => return emitted,
+        };
+
+        let direct = direct_light(r, &rec, scene);
+        let mut weight = sample.weight;
+        if bounces + 1 >= self.roulette_depth {
+            match roulette(throughput * weight) {
+                Some(boost) => weight = boost * weight,
+                None => return emitted + direct,
+            }
+        }
+
+        let scattered = Ray::new(rec.p, sample.direction);
+        let bounce = Bounce::after(&rec, sample.pdf, sample.lobe);
+        let indirect = self.trace(
+            &scattered,
+            scene,
+            depth,
+            bounces + 1,
+            throughput * weight,
+            bounce,
+        );
+        emitted + direct + weight * indirect
+    }
+}
+
+impl Default for SimplePathIntegrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Integrator for SimplePathIntegrator {
+    fn ray_color(&self, r: &Ray, scene: &Scene, depth: u64) -> Color {
+        self.trace(r, scene, depth, 0, Color::new(1.0, 1.0, 1.0), None)
+    }
+}
+
+/// A path tracer that follows each bounce in a loop, keeping track of
+/// how much light still gets through the path so far. It gives the
+/// same results as [`SimplePathIntegrator`], at any depth, and ends
+/// paths with Russian roulette the same way.
+#[derive(Clone, Copy, Debug)]
+pub struct PathIntegrator {
+    roulette_depth: u64,
+}
+
+impl PathIntegrator {
+    pub fn new() -> Self {
+        Self {
+            roulette_depth: DEFAULT_ROULETTE_DEPTH,
+        }
+    }
+
+    /// Sets the number of bounces after which Russian roulette starts.
+    /// Set it to the depth limit to never end paths early.
+    pub fn with_roulette_depth(mut self, roulette_depth: u64) -> Self {
+        self.roulette_depth = roulette_depth;
+        self
+    }
+}
+
+impl Default for PathIntegrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn ray_color(&self, r: &Ray, scene: &Scene, depth: u64) -> Color {
+        let mut color = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = Ray::new(r.origin(), r.direction());
+        let mut bounce = None;
+
+        for bounces in 0..depth {
+            let rec = match scene.world().hit(&ray, 0.001, f64::INFINITY) {
+                Some(rec) => rec,
+                None => {
+                    color += throughput * background_light(&ray, scene, bounce);
+                    break;
+                }
+            };
+
+            color += throughput * emitted_light(&ray, &rec, scene, bounce);
+            let sample = match rec.mat.sample(&ray, &rec) {
+                Some(sample) => sample,
+                None => break,
+            };
+
+            color += throughput * direct_light(&ray, &rec, scene);
+            throughput = throughput * sample.weight;
+            if bounces + 1 >= self.roulette_depth {
+                match roulette(throughput) {
+                    Some(boost) => throughput = boost * throughput,
+                    None => break,
+                }
+            }
+
+            bounce = Bounce::after(&rec, sample.pdf, sample.lobe);
+            ray = Ray::new(rec.p, sample.direction);
+        }
+
+        color
+    }
+}
+
+/// Decides at random whether a path that lets through `throughput` of
+/// the light it finds keeps going, favoring paths that let through
+/// more. Returns how much brighter a surviving path has to be made,
+/// or none if the path ends.
+fn roulette(throughput: Color) -> Option<f64> {
+    let survival = throughput
+        .x()
+        .max(throughput.y())
+        .max(throughput.z())
+        .min(1.0);
+
+    if survival > 0.0 && random::thread_rng().gen::<f64>() < survival {
+        Some(1.0 / survival)
+    } else {
+        None
+    }
+}
+
+/// How a ray came to be scattered off a surface, for weighing
+/// the light it finds against what was sampled directly there.
+#[derive(Clone, Copy)]
+struct Bounce {
+    origin: Point3,
+    /// The density with which the material picked the ray's direction.
+    pdf: f64,
+}
+
+impl Bounce {
+    /// Specular lobes can't be sampled directly, so rays picked
+    /// from them don't have anything to be weighed against.
+    fn after(rec: &HitRecord, pdf: f64, lobe: Lobes) -> Option<Self> {
+        if lobe.is_specular() {
+            None
+        } else {
+            Some(Self { origin: rec.p, pdf })
+        }
+    }
+}
+
+/// The light given off by the surface `r` hit, weighed against
+/// the chance that it was already sampled directly.
+fn emitted_light(r: &Ray, rec: &HitRecord, scene: &Scene, bounce: Option<Bounce>) -> Color {
+    let weight = bounce.map_or(1.0, |b| {
+        power_heuristic(b.pdf, scene.lights().pdf_value(b.origin, r.direction()))
+    });
+    weight * rec.mat.emitted(rec)
+}
+
+/// The light from the background, seen by a ray that
+/// missed everything, weighed like [`emitted_light`].
+fn background_light(r: &Ray, scene: &Scene, bounce: Option<Bounce>) -> Color {
+    let background = scene.background();
+    let weight = bounce.map_or(1.0, |b| {
+        power_heuristic(b.pdf, background.pdf_value(r.direction()))
+    });
+    weight * background.color(r.direction())
+}
+
+/// The light scattered at the hit that comes straight from the lights
+/// and background. Specular lobes scatter in exact directions that
+/// can't be sampled directly, so only the other lobes gather any.
+fn direct_light(r: &Ray, rec: &HitRecord, scene: &Scene) -> Color {
+    if rec.mat.lobes().intersects(Lobes::DIFFUSE | Lobes::GLOSSY) {
+        sample_lights(r, rec, scene) + sample_background(r, rec, scene)
+    } else {
+        Color::new(0.0, 0.0, 0.0)
+    }
+}
+
+/// Estimates the light scattered at the hit from the scene's lights, by
+/// casting a ray toward a random point on one of them.
+fn sample_lights(r: &Ray, rec: &HitRecord, scene: &Scene) -> Color {
+    let lights = scene.lights();
+    if lights.is_empty() {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let direction = lights.random(rec.p);
+    let pdf = lights.pdf_value(rec.p, direction);
+
+    let shadow_ray = Ray::new(rec.p, direction);
+    let emitted = match scene.world().hit(&shadow_ray, 0.001, f64::INFINITY) {
+        // whatever the ray hits first is the light that makes it through
+        Some(light) if pdf > 0.0 => light.mat.emitted(&light),
+        _ => return Color::new(0.0, 0.0, 0.0),
+    };
+
+    let weight = power_heuristic(pdf, rec.mat.pdf(r, rec, direction));
+    weight * rec.mat.eval(r, rec, direction) * emitted / pdf
+}
+
+/// Estimates the background light scattered at the hit by casting a ray
+/// toward a direction picked by the background.
+fn sample_background(r: &Ray, rec: &HitRecord, scene: &Scene) -> Color {
+    let background = scene.background();
+    let direction = match background.random() {
+        Some(direction) => direction,
+        None => return Color::new(0.0, 0.0, 0.0),
+    };
+    let pdf = background.pdf_value(direction);
+
+    let shadow_ray = Ray::new(rec.p, direction);
+    if pdf <= 0.0
+        || scene
+            .world()
+            .hit(&shadow_ray, 0.001, f64::INFINITY)
+            .is_some()
+    {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    let weight = power_heuristic(pdf, rec.mat.pdf(r, rec, direction));
+    weight * rec.mat.eval(r, rec, direction) * background.color(direction) / pdf
+}
@@ -1,6 +1,30 @@
+pub mod aabb;
+pub mod background;
+pub mod bvh;
 pub mod camera;
+pub mod cone;
+pub mod cuboid;
+pub mod cylinder;
+pub mod description;
+pub mod disk;
+pub mod distribution;
 pub mod hit;
+pub mod import;
+pub mod instance;
+pub mod integrator;
 pub mod material;
+pub mod math;
+pub mod mesh;
+pub mod onb;
+pub mod plane;
+pub mod quad;
+pub mod random;
 pub mod ray;
+pub mod renderer;
+pub mod scene;
 pub mod sphere;
+pub mod texture;
+pub mod torus;
+pub mod transform;
+pub mod triangle;
 pub mod vec;
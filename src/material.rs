@@ -1,18 +1,117 @@
 //! Provides a material trait
 
+use std::f64::consts::PI;
+use std::ops::BitOr;
+use std::sync::Arc;
+
 use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 use super::hit::HitRecord;
+use super::random;
 use super::ray::Ray;
+use super::texture::Texture;
 use super::vec::Color;
 use crate::vec::Vec3;
 
+/// A set of the kinds of scattering a material does, which
+/// integrators use to tell how a material can be sampled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lobes(u8);
+
+impl Lobes {
+    pub const NONE: Self = Self(0);
+    /// Scattering in every direction over the hemisphere.
+    pub const DIFFUSE: Self = Self(1);
+    /// Scattering spread out around a preferred direction.
+    pub const GLOSSY: Self = Self(1 << 1);
+    /// Scattering in a single, exact direction, which
+    /// can't be hit by sampling anything else.
+    pub const SPECULAR: Self = Self(1 << 2);
+    /// Scattering through the surface, rather than off of it.
+    pub const TRANSMISSION: Self = Self(1 << 3);
+
+    /// Returns whether every lobe in `other` is in the set.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns whether any lobe in `other` is in the set.
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn is_specular(self) -> bool {
+        self.contains(Self::SPECULAR)
+    }
+}
+
+impl BitOr for Lobes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A direction picked by [`Scatter::sample`], along with what
+/// it takes to weigh the light arriving from it.
+#[derive(Clone, Copy, Debug)]
+pub struct BsdfSample {
+    /// The direction the ray bounces off in, from the hit point.
+    pub direction: Vec3,
+    /// The value of `eval` for the direction, divided by `pdf`. This is
+    /// how much of the light arriving from the direction gets through.
+    pub weight: Color,
+    /// The probability density, over solid angle, of picking the direction.
+    /// For specular lobes, it's the probability of picking that exact
+    /// direction out of the few possible ones instead.
+    pub pdf: f64,
+    /// The lobe the direction was picked from.
+    pub lobe: Lobes,
+}
+
+/// A material, which describes how light bounces off (or through) a
+/// surface, and what light the surface gives off by itself.
+///
+/// Directions all point away from the hit point, and don't need to be
+/// normalized. Light flows against them, so evaluating a direction
+/// tells how much light arriving from it scatters back along `r_in`.
 pub trait Scatter: Send + Sync {
-    /// Runs when an object is "hit", yielding a new, "bounced" ray and a color.
-    /// Returns none if the ray isn't bouncing again.
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
+    /// The kinds of scattering the material does.
+    fn lobes(&self) -> Lobes;
+
+    /// Picks a direction to bounce the ray off in, when an object
+    /// is "hit". Returns none if the ray isn't bouncing again.
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample>;
+
+    /// The fraction of light arriving from `direction` that gets scattered
+    /// back along `r_in`, including the cosine falloff. Specular lobes
+    /// only scatter in exact directions, so they never contribute here.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /// The probability density, over solid angle, with which `sample` picks
+    /// `direction`. Like `eval`, this leaves out any specular lobes.
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// The light given off by the surface at the hit point.
+    /// Most materials don't give off any.
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
 }
 
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Lambertian {
     albedo: Color,
 }
@@ -21,24 +120,53 @@ impl Lambertian {
     pub fn new(albedo: Color) -> Self {
         Lambertian { albedo }
     }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        // vertex colors tint the albedo, like a texture would
+        rec.color.map_or(self.albedo, |c| self.albedo * c)
+    }
 }
 
 impl Scatter for Lambertian {
-    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn lobes(&self) -> Lobes {
+        Lobes::DIFFUSE
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
         // let scatter_direction = rec.p + rec.normal + Vec3::random_in_unit_sphere();
-        let mut scatter_direction = rec.normal + Vec3::random_in_unit_sphere().normalized();
+        let mut scatter_direction = rec.shading_normal + Vec3::random_in_unit_sphere().normalized();
         // let target = rec.p + Vec3::random_in_hemisphere(rec.normal);
 
         // catch degenerate scatter direction
         if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
+            scatter_direction = rec.shading_normal;
         }
-        let scattered = Ray::new(rec.p, scatter_direction);
 
-        Some((self.albedo, scattered))
+        Some(BsdfSample {
+            direction: scatter_direction,
+            weight: self.albedo(rec),
+            pdf: self.pdf(r_in, rec, scatter_direction),
+            lobe: Lobes::DIFFUSE,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.albedo(rec) * self.pdf(r_in, rec, direction)
+    }
+
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        // the scattered directions are cosine-distributed around the normal
+        let cosine = rec.shading_normal.dot(direction.normalized());
+        if cosine < 0.0 {
+            0.0
+        } else {
+            cosine / PI
+        }
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Metal {
     albedo: Color,
     fuzz: f64,
@@ -51,18 +179,67 @@ impl Metal {
 }
 
 impl Scatter for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let reflected = r_in.direction().reflect(rec.normal).normalized();
-        let scattered = Ray::new(rec.p, reflected + self.fuzz * Vec3::random_in_unit_sphere());
+    fn lobes(&self) -> Lobes {
+        if self.fuzz > 0.0 {
+            Lobes::GLOSSY
+        } else {
+            Lobes::SPECULAR
+        }
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let reflected = r_in.direction().reflect(rec.shading_normal).normalized();
+        let direction = reflected + self.fuzz * Vec3::random_in_unit_sphere();
+
+        if direction.dot(rec.normal) <= 0.0 {
+            return None;
+        }
 
-        if scattered.direction().dot(rec.normal) > 0.0 {
-            Some((self.albedo, scattered))
+        let (pdf, lobe) = if self.fuzz > 0.0 {
+            (self.pdf(r_in, rec, direction), Lobes::GLOSSY)
         } else {
-            None
+            (1.0, Lobes::SPECULAR)
+        };
+        Some(BsdfSample {
+            direction,
+            weight: self.albedo,
+            pdf,
+            lobe,
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        self.albedo * self.pdf(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let direction = direction.normalized();
+        if self.fuzz <= 0.0 || direction.dot(rec.normal) <= 0.0 {
+            return 0.0;
+        }
+
+        // the fuzzed directions point at random points in a ball around
+        // the reflection, so the density is the share of the ball's volume
+        // the direction passes through, found by intersecting it
+        let reflected = r_in.direction().reflect(rec.shading_normal).normalized();
+        let b = direction.dot(reflected);
+        let discriminant = b * b - 1.0 + self.fuzz * self.fuzz;
+        if discriminant <= 0.0 {
+            return 0.0;
         }
+
+        let far = b + discriminant.sqrt();
+        let near = (b - discriminant.sqrt()).max(0.0);
+        if far <= 0.0 {
+            return 0.0;
+        }
+
+        (far.powi(3) - near.powi(3)) / (4.0 * PI * self.fuzz.powi(3))
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Dielectric {
     index_of_refraction: f64,
 }
@@ -77,12 +254,16 @@ impl Dielectric {
     fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
         // use schlick's approximation for reflectance
         let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
-        r0 * (1.0 - r0) * (1.0 - cosine).powi(5)
+        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
     }
 }
 
 impl Scatter for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn lobes(&self) -> Lobes {
+        Lobes::SPECULAR | Lobes::TRANSMISSION
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
         let refraction_ratio = if rec.front_face {
             1.0 / self.index_of_refraction
         } else {
@@ -90,20 +271,206 @@ impl Scatter for Dielectric {
         };
 
         let unit_direction = r_in.direction().normalized();
-        let cos_theta = (-unit_direction).dot(rec.normal).min(1.0);
+        let cos_theta = (-unit_direction).dot(rec.shading_normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let must_reflect =
-            Self::reflectance(cos_theta, refraction_ratio) > rand::thread_rng().gen();
+        let reflectance = if cannot_refract {
+            1.0
+        } else {
+            Self::reflectance(cos_theta, refraction_ratio)
+        };
 
-        let direction = if cannot_refract || must_reflect {
-            unit_direction.reflect(rec.normal)
+        let (direction, pdf, lobe) = if reflectance > random::thread_rng().gen() {
+            (
+                unit_direction.reflect(rec.shading_normal),
+                reflectance,
+                Lobes::SPECULAR,
+            )
         } else {
-            unit_direction.refract(rec.normal, refraction_ratio)
+            (
+                unit_direction.refract(rec.shading_normal, refraction_ratio),
+                1.0 - reflectance,
+                Lobes::SPECULAR | Lobes::TRANSMISSION,
+            )
         };
 
-        let scattered = Ray::new(rec.p, direction);
-        Some((Color::new(1.0, 1.0, 1.0), scattered))
+        Some(BsdfSample {
+            direction,
+            weight: Color::new(1.0, 1.0, 1.0),
+            pdf,
+            lobe,
+        })
+    }
+}
+
+/// A surface that gives off light in every direction, from both
+/// of its sides. It doesn't reflect any light itself.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self { emit }
+    }
+}
+
+impl Scatter for DiffuseLight {
+    fn lobes(&self) -> Lobes {
+        Lobes::NONE
+    }
+
+    fn sample(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<BsdfSample> {
+        None
+    }
+
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        self.emit
+    }
+}
+
+/// The metallic-roughness material that glTF and most modelling tools
+/// use. It's a blend of a diffuse surface and a metal of the same base
+/// color, with as much of the metal as its metallic value says, and
+/// fuzzier reflections the rougher it is. The base color, metallic and
+/// roughness values can each vary across the surface with a texture.
+pub struct MetallicRoughness {
+    base_color: Color,
+    metallic: f64,
+    roughness: f64,
+    emission: Color,
+    base_color_texture: Option<Arc<dyn Texture>>,
+    metallic_roughness_texture: Option<Arc<dyn Texture>>,
+    emissive_texture: Option<Arc<dyn Texture>>,
+}
+
+impl MetallicRoughness {
+    /// Creates a material that doesn't give off any light,
+    /// with `metallic` and `roughness` from 0 to 1.
+    pub fn new(base_color: Color, metallic: f64, roughness: f64) -> Self {
+        Self {
+            base_color,
+            metallic,
+            roughness,
+            emission: Color::new(0.0, 0.0, 0.0),
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            emissive_texture: None,
+        }
+    }
+
+    /// Multiplies the base color by a texture.
+    pub fn with_base_color_texture(mut self, texture: Arc<dyn Texture>) -> Self {
+        self.base_color_texture = Some(texture);
+        self
+    }
+
+    /// Multiplies the metallic and roughness values by a texture's
+    /// blue and green channels, the way glTF packs them.
+    pub fn with_metallic_roughness_texture(mut self, texture: Arc<dyn Texture>) -> Self {
+        self.metallic_roughness_texture = Some(texture);
+        self
+    }
+
+    /// Sets the light the surface gives off.
+    pub fn with_emission(mut self, emission: Color) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    /// Multiplies the light the surface gives off by a texture.
+    pub fn with_emissive_texture(mut self, texture: Arc<dyn Texture>) -> Self {
+        self.emissive_texture = Some(texture);
+        self
+    }
+
+    /// The diffuse and metal parts of the material at the
+    /// hit point, and how much of it is metal.
+    fn parts(&self, rec: &HitRecord) -> (Lambertian, Metal, f64) {
+        let texture = |texture: &Option<Arc<dyn Texture>>| {
+            texture
+                .as_ref()
+                .map_or(Color::new(1.0, 1.0, 1.0), |t| t.value(rec.u, rec.v, rec.p))
+        };
+
+        let base_color = self.base_color * texture(&self.base_color_texture);
+        let metal_roughness = texture(&self.metallic_roughness_texture);
+        let metallic = (self.metallic * metal_roughness.z()).clamp(0.0, 1.0);
+        let roughness = (self.roughness * metal_roughness.y()).clamp(0.0, 1.0);
+
+        // the diffuse part is tinted by vertex colors on its own
+        let tint = rec.color.unwrap_or(Color::new(1.0, 1.0, 1.0));
+        // roughness is meant to look even, from smooth to rough,
+        // which takes squaring it to get the amount of fuzz
+        let metal = Metal::new(base_color * tint, roughness * roughness);
+        (Lambertian::new(base_color), metal, metallic)
+    }
+}
+
+impl Scatter for MetallicRoughness {
+    fn lobes(&self) -> Lobes {
+        // the texture can only make the surface less metallic, or smoother
+        let textured = self.metallic_roughness_texture.is_some();
+        let mut lobes = Lobes::NONE;
+        if self.metallic < 1.0 || textured {
+            lobes = lobes | Lobes::DIFFUSE;
+        }
+        if self.metallic > 0.0 && self.roughness > 0.0 {
+            lobes = lobes | Lobes::GLOSSY;
+        }
+        if self.metallic > 0.0 && (self.roughness <= 0.0 || textured) {
+            lobes = lobes | Lobes::SPECULAR;
+        }
+        lobes
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let (diffuse, metal, metallic) = self.parts(rec);
+
+        let sample = if metallic > random::thread_rng().gen() {
+            let sample = metal.sample(r_in, rec)?;
+            if sample.lobe.is_specular() {
+                // only the metal reflects in exactly this direction
+                return Some(BsdfSample {
+                    pdf: metallic * sample.pdf,
+                    ..sample
+                });
+            }
+            sample
+        } else {
+            diffuse.sample(r_in, rec)?
+        };
+
+        let pdf = self.pdf(r_in, rec, sample.direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            weight: self.eval(r_in, rec, sample.direction) / pdf,
+            pdf,
+            ..sample
+        })
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Color {
+        let (diffuse, metal, metallic) = self.parts(rec);
+        (1.0 - metallic) * diffuse.eval(r_in, rec, direction)
+            + metallic * metal.eval(r_in, rec, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f64 {
+        let (diffuse, metal, metallic) = self.parts(rec);
+        (1.0 - metallic) * diffuse.pdf(r_in, rec, direction)
+            + metallic * metal.pdf(r_in, rec, direction)
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        match &self.emissive_texture {
+            Some(texture) => self.emission * texture.value(rec.u, rec.v, rec.p),
+            None => self.emission,
+        }
     }
 }
@@ -0,0 +1,339 @@
+//! Finds the real roots of polynomials up to degree four, for
+//! intersecting rays with surfaces given by polynomial equations.
+//!
+//! Past quadratics, the closed forms lose too much precision when
+//! roots are close together, which is just where rays graze a
+//! surface. Instead, the roots of the derivative split the line into
+//! pieces where the polynomial only rises or only falls, and each
+//! piece that crosses zero is searched with Newton's method, falling
+//! back to bisection when a step would leave the piece.
+
+use std::ops::Deref;
+
+/// The most steps spent narrowing down any one root.
+const MAX_STEPS: usize = 100;
+
+/// The real roots of a polynomial, in increasing order.
+///
+/// Roots where the polynomial only touches zero without crossing
+/// it are only found when it's exactly zero there, which is fine
+/// for ray tracing, where they're rays just grazing a surface.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Roots {
+    values: [f64; 4],
+    len: usize,
+}
+
+impl Roots {
+    fn push(&mut self, x: f64) {
+        self.values[self.len] = x;
+        self.len += 1;
+    }
+}
+
+impl Deref for Roots {
+    type Target = [f64];
+
+    fn deref(&self) -> &[f64] {
+        &self.values[..self.len]
+    }
+}
+
+/// Solves `a x² + b x + c = 0`, avoiding the cancellation
+/// that the schoolbook formula suffers when `b² ≫ 4ac`.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Roots {
+    let mut roots = Roots::default();
+    if a == 0.0 {
+        if b != 0.0 {
+            roots.push(-c / b);
+        }
+        return roots;
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return roots;
+    }
+
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (x0, x1) = if q != 0.0 {
+        (q / a, c / q)
+    } else {
+        // b and c are both zero
+        (0.0, 0.0)
+    };
+    roots.push(x0.min(x1));
+    roots.push(x0.max(x1));
+    roots
+}
+
+/// Solves `a x³ + b x² + c x + d = 0`.
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Roots {
+    if a == 0.0 {
+        return solve_quadratic(b, c, d);
+    }
+
+    let turns = solve_quadratic(3.0 * a, 2.0 * b, c);
+    solve_between_turns(&[a, b, c, d], &turns)
+}
+
+/// Solves `a x⁴ + b x³ + c x² + d x + e = 0`.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Roots {
+    if a == 0.0 {
+        return solve_cubic(b, c, d, e);
+    }
+
+    let turns = solve_cubic(4.0 * a, 3.0 * b, 2.0 * c, d);
+    solve_between_turns(&[a, b, c, d, e], &turns)
+}
+
+/// Finds the roots of the polynomial with the given coefficients,
+/// highest degree first and non-zero, given the roots of its
+/// derivative, where it turns around.
+fn solve_between_turns(coefficients: &[f64], turns: &[f64]) -> Roots {
+    // every root is within this distance of zero
+    let bound = 1.0
+        + coefficients[1..]
+            .iter()
+            .map(|c| (c / coefficients[0]).abs())
+            .fold(0.0, f64::max);
+
+    let mut points = [0.0; 5];
+    points[0] = -bound;
+    let mut len = 1;
+    for &turn in turns {
+        points[len] = turn.clamp(-bound, bound);
+        len += 1;
+    }
+    points[len] = bound;
+    let points = &points[..=len];
+
+    let mut roots = Roots::default();
+    for (i, &x) in points.iter().enumerate() {
+        let (value, _) = evaluate(coefficients, x);
+        if value == 0.0 && roots.last() != Some(&x) {
+            roots.push(x);
+        }
+
+        if let Some(&next) = points.get(i + 1) {
+            let (next_value, _) = evaluate(coefficients, next);
+            if value * next_value < 0.0 {
+                roots.push(find_root(coefficients, x, next, value));
+            }
+        }
+    }
+
+    roots
+}
+
+/// Narrows down the root between `lo` and `hi`, where the
+/// polynomial crosses zero once, starting out at `lo_value`.
+fn find_root(coefficients: &[f64], mut lo: f64, mut hi: f64, lo_value: f64) -> f64 {
+    let mut x = 0.5 * (lo + hi);
+    for _ in 0..MAX_STEPS {
+        let (value, slope) = evaluate(coefficients, x);
+        if value == 0.0 {
+            return x;
+        }
+
+        // keep the root between lo and hi
+        if (value < 0.0) == (lo_value < 0.0) {
+            lo = x;
+        } else {
+            hi = x;
+        }
+
+        let newton = x - value / slope;
+        let next = if newton > lo && newton < hi {
+            newton
+        } else {
+            0.5 * (lo + hi)
+        };
+
+        if (next - x).abs() <= f64::EPSILON * x.abs() {
+            return next;
+        }
+        x = next;
+    }
+
+    x
+}
+
+/// The value and the slope of the polynomial with the given
+/// coefficients, highest degree first, at `x`, by Horner's scheme.
+fn evaluate(coefficients: &[f64], x: f64) -> (f64, f64) {
+    let (mut value, mut slope) = (0.0, 0.0);
+    for &c in coefficients {
+        slope = slope * x + value;
+        value = value * x + c;
+    }
+
+    (value, slope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The coefficients of the polynomial with the given roots and a
+    /// leading coefficient of 1, highest degree first.
+    fn from_roots(roots: &[f64]) -> Vec<f64> {
+        let mut coefficients = vec![1.0];
+        for &root in roots {
+            coefficients.push(0.0);
+            for i in (1..coefficients.len()).rev() {
+                coefficients[i] -= root * coefficients[i - 1];
+            }
+        }
+        coefficients
+    }
+
+    fn assert_roots(found: &[f64], expected: &[f64], tolerance: f64) {
+        assert_eq!(
+            found.len(),
+            expected.len(),
+            "found {found:?}, expected {expected:?}"
+        );
+        for (x, y) in found.iter().zip(expected) {
+            assert!(
+                (x - y).abs() <= tolerance,
+                "found {found:?}, expected {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn quadratic_known_roots() {
+        assert_roots(&solve_quadratic(1.0, -4.0, 3.0), &[1.0, 3.0], 1e-12);
+        assert_roots(&solve_quadratic(-2.0, 0.0, 8.0), &[-2.0, 2.0], 1e-12);
+        assert_roots(&solve_quadratic(0.0, 2.0, -1.0), &[0.5], 1e-12);
+        assert_roots(&solve_quadratic(1.0, 0.0, 1.0), &[], 0.0);
+        assert_roots(&solve_quadratic(0.0, 0.0, 1.0), &[], 0.0);
+    }
+
+    #[test]
+    fn quadratic_keeps_small_root_precise() {
+        // the schoolbook formula loses every digit of the small root here
+        let roots = solve_quadratic(1.0, -1e8, 1.0);
+        assert_eq!(roots.len(), 2);
+        assert!((roots[0] - 1e-8).abs() <= 1e-8 * 1e-12);
+        assert!((roots[1] - 1e8).abs() <= 1e8 * 1e-12);
+    }
+
+    #[test]
+    fn cubic_known_roots() {
+        let c = from_roots(&[-2.0, 0.5, 3.0]);
+        assert_roots(
+            &solve_cubic(c[0], c[1], c[2], c[3]),
+            &[-2.0, 0.5, 3.0],
+            1e-12,
+        );
+
+        // x³ + x + 1 has a single real root
+        assert_roots(
+            &solve_cubic(1.0, 0.0, 1.0, 1.0),
+            &[-0.6823278038280193],
+            1e-12,
+        );
+        assert_roots(&solve_cubic(0.0, 1.0, -4.0, 3.0), &[1.0, 3.0], 1e-12);
+    }
+
+    #[test]
+    fn quartic_known_roots() {
+        let c = from_roots(&[1.0, 2.0, 3.0, 4.0]);
+        assert_roots(
+            &solve_quartic(c[0], c[1], c[2], c[3], c[4]),
+            &[1.0, 2.0, 3.0, 4.0],
+            1e-12,
+        );
+
+        let c = from_roots(&[-10.0, -0.1, 0.1, 10.0]);
+        assert_roots(
+            &solve_quartic(2.0 * c[0], 2.0 * c[1], 2.0 * c[2], 2.0 * c[3], 2.0 * c[4]),
+            &[-10.0, -0.1, 0.1, 10.0],
+            1e-12,
+        );
+
+        // x⁴ + 1 never reaches zero
+        assert_roots(&solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[], 0.0);
+    }
+
+    #[test]
+    fn repeated_roots() {
+        assert_roots(&solve_quadratic(1.0, -4.0, 4.0), &[2.0, 2.0], 0.0);
+
+        // a double root touching zero, next to one crossing it
+        let c = from_roots(&[1.0, 1.0, 3.0]);
+        assert_roots(&solve_cubic(c[0], c[1], c[2], c[3]), &[1.0, 3.0], 1e-12);
+
+        let c = from_roots(&[2.0, 2.0, 2.0]);
+        assert_roots(&solve_cubic(c[0], c[1], c[2], c[3]), &[2.0], 1e-12);
+
+        let c = from_roots(&[-1.0, 0.0, 0.0, 0.0]);
+        assert_roots(
+            &solve_quartic(c[0], c[1], c[2], c[3], c[4]),
+            &[-1.0, 0.0],
+            1e-12,
+        );
+    }
+
+    /// The quartic `Torus::hit` solves, for a torus around the Z axis
+    /// with a major radius of 1, and a ray from `o` along the unit `d`.
+    fn torus(o: [f64; 3], d: [f64; 3], minor: f64) -> Roots {
+        let f = o[0] * d[0] + o[1] * d[1] + o[2] * d[2];
+        let e = o[0] * o[0] + o[1] * o[1] + o[2] * o[2] - 1.0 - minor * minor;
+        solve_quartic(
+            1.0,
+            4.0 * f,
+            2.0 * e + 4.0 * f * f + 4.0 * d[2] * d[2],
+            4.0 * f * e + 8.0 * o[2] * d[2],
+            e * e - 4.0 * (minor * minor - o[2] * o[2]),
+        )
+    }
+
+    #[test]
+    fn torus_through_the_middle() {
+        // in one side of the tube and out the other, twice
+        let roots = torus([-2.0, 0.0, 0.0], [1.0, 0.0, 0.0], 0.25);
+        assert_roots(&roots, &[0.75, 1.25, 2.75, 3.25], 1e-12);
+    }
+
+    #[test]
+    fn torus_near_tangent() {
+        let minor = 0.25;
+        for gap in [1e-3, 1e-6, 1e-9] {
+            // just under the top of the tube, the ray passes
+            // through a thin slice of it on each side
+            let z = minor - gap;
+            let roots = torus([-2.0, 0.0, z], [1.0, 0.0, 0.0], minor);
+            let half_chord = (minor * minor - z * z).sqrt();
+            let expected = [
+                1.0 - half_chord,
+                1.0 + half_chord,
+                3.0 - half_chord,
+                3.0 + half_chord,
+            ];
+            assert_roots(&roots, &expected, 1e-7);
+
+            // and just over it, it misses
+            let roots = torus([-2.0, 0.0, minor + gap], [1.0, 0.0, 0.0], minor);
+            assert_roots(&roots, &[], 0.0);
+        }
+    }
+
+    #[test]
+    fn torus_grazing_the_hole() {
+        // just inside the hole, the ray leaves the tube
+        // for a moment as it passes the inner edge
+        let minor = 0.25;
+        let y = 1.0 - minor - 1e-9;
+        let roots = torus([-2.0, y, 0.0], [1.0, 0.0, 0.0], minor);
+        let inner = ((1.0 - minor).powi(2) - y * y).sqrt();
+        let outer = ((1.0 + minor).powi(2) - y * y).sqrt();
+        assert_roots(
+            &roots,
+            &[2.0 - outer, 2.0 - inner, 2.0 + inner, 2.0 + outer],
+            1e-7,
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Provides a triangle mesh, which stores its vertex data once
+//! and has its triangles refer to it by index. The triangles are
+//! kept in their own bounding volume hierarchy, so a mesh can be
+//! put into a `World` as a single object.
+//!
+//! Meshes are usually loaded from files, using one of the submodules.
+
+pub mod mtl;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::bvh::{BvhBuilder, BvhStats, BvhTree, SplitMethod};
+use super::distribution::Distribution1D;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::random;
+use super::ray::Ray;
+use super::triangle;
+use super::vec::{Color, Point3, Vec3};
+
+/// A triangle of a mesh, made of indices into the mesh's vertex data.
+#[derive(Clone, Copy, Debug)]
+pub struct MeshTriangle {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    /// Index of the group the triangle belongs to.
+    pub group: usize,
+    /// Index of the triangle's material in the mesh's material list.
+    pub material: usize,
+}
+
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    /// Per-vertex colors, indexed like `positions`, or empty.
+    colors: Vec<Color>,
+    triangles: Vec<MeshTriangle>,
+    groups: Vec<String>,
+    materials: Vec<Arc<dyn Scatter>>,
+    tree: BvhTree,
+    /// Picks triangles in proportion to their area, for sampling
+    /// points uniformly over the whole mesh.
+    areas: Distribution1D,
+    area: f64,
+}
+
+impl TriangleMesh {
+    /// Creates a mesh from its vertex data and triangles.
+    /// Every triangle gets the same material, unless a list
+    /// is given with [`TriangleMesh::with_materials`].
+    ///
+    /// # Panics
+    /// If a triangle refers to a vertex that doesn't exist.
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        triangles: Vec<MeshTriangle>,
+        mat: Arc<dyn Scatter>,
+    ) -> Self {
+        let bounds: Vec<Aabb> = triangles
+            .iter()
+            .map(|tri| triangle::bounding_box(tri.positions.map(|i| positions[i])))
+            .collect();
+
+        // meshes are usually large and dense, which is
+        // where the slower to build SAH trees pay off
+        let builder = BvhBuilder::new()
+            .split_method(SplitMethod::Sah { bins: 16 })
+            .max_leaf_size(4);
+        let (tree, order) = BvhTree::build(&builder, &bounds);
+        let triangles: Vec<MeshTriangle> = order.into_iter().map(|i| triangles[i]).collect();
+
+        let areas: Vec<f64> = triangles
+            .iter()
+            .map(|tri| triangle::area(tri.positions.map(|i| positions[i])))
+            .collect();
+        let area = areas.iter().sum();
+
+        Self {
+            positions,
+            normals,
+            uvs,
+            colors: Vec::new(),
+            triangles,
+            groups: vec![String::from("default")],
+            materials: vec![mat],
+            tree,
+            areas: Distribution1D::new(areas),
+            area,
+        }
+    }
+
+    /// Names the groups that the triangles' `group` indices refer to.
+    pub fn with_groups(mut self, groups: Vec<String>) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// Sets the materials that the triangles' `material` indices refer to.
+    ///
+    /// # Panics
+    /// If a triangle refers to a material that isn't in the list.
+    pub fn with_materials(mut self, materials: Vec<Arc<dyn Scatter>>) -> Self {
+        assert!(self
+            .triangles
+            .iter()
+            .all(|tri| tri.material < materials.len()));
+
+        self.materials = materials;
+        self
+    }
+
+    /// Gives every vertex a color, which gets interpolated across the
+    /// triangles and handed to their materials through the `HitRecord`.
+    ///
+    /// # Panics
+    /// If there isn't exactly one color per vertex position.
+    pub fn with_colors(mut self, colors: Vec<Color>) -> Self {
+        assert_eq!(colors.len(), self.positions.len());
+
+        self.colors = colors;
+        self
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn groups(&self) -> &[String] {
+        &self.groups
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        self.tree.stats()
+    }
+
+    /// Moves every vertex with `point` and turns every normal with
+    /// `normal`, rebuilding the mesh's tree to match. If `flip` is set,
+    /// the triangles' corners are put in the opposite order, which
+    /// turns their front faces around.
+    pub(crate) fn map_vertices<P, N>(self, point: P, normal: N, flip: bool) -> Self
+    where
+        P: Fn(Point3) -> Point3,
+        N: Fn(Vec3) -> Vec3,
+    {
+        let positions = self.positions.into_iter().map(point).collect();
+        let normals = self.normals.into_iter().map(normal).collect();
+        let mut triangles = self.triangles;
+        if flip {
+            for tri in &mut triangles {
+                tri.positions.swap(1, 2);
+                if let Some(normals) = &mut tri.normals {
+                    normals.swap(1, 2);
+                }
+                if let Some(uvs) = &mut tri.uvs {
+                    uvs.swap(1, 2);
+                }
+            }
+        }
+
+        let mesh = Self::new(
+            positions,
+            normals,
+            self.uvs,
+            triangles,
+            self.materials[0].clone(),
+        );
+        Self {
+            colors: self.colors,
+            groups: self.groups,
+            materials: self.materials,
+            ..mesh
+        }
+    }
+}
+
+impl Hit for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.tree.hit(r, t_min, t_max, |i, closest_so_far| {
+            let tri = &self.triangles[i];
+            let v = tri.positions.map(|j| self.positions[j]);
+            let (t, b1, b2) = triangle::intersect(r, v, t_min, closest_so_far)?;
+            let b0 = 1.0 - b1 - b2;
+
+            let mut rec = triangle::hit_record(
+                r,
+                t,
+                [b0, b1, b2],
+                v,
+                tri.normals.map(|n| n.map(|j| self.normals[j])),
+                tri.uvs.map(|uv| uv.map(|j| self.uvs[j])),
+                &*self.materials[tri.material],
+            );
+            if !self.colors.is_empty() {
+                let c = tri.positions.map(|j| self.colors[j]);
+                rec.color = Some(b0 * c[0] + b1 * c[1] + b2 * c[2]);
+            }
+
+            Some(rec)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.tree.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.area <= 0.0 {
+            return 0.0;
+        }
+
+        // a direction can pass through several of the triangles,
+        // and picking any of them could have led to it
+        let r = Ray::new(origin, direction);
+        let mut sum = 0.0;
+        self.tree.hit(&r, 0.001, f64::INFINITY, |i, _| {
+            let v = self.triangles[i].positions.map(|j| self.positions[j]);
+            if let Some((t, _, _)) = triangle::intersect(&r, v, 0.001, f64::INFINITY) {
+                sum += triangle::solid_angle_factor(&r, t, v);
+            }
+            None
+        });
+
+        sum / self.area
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        if self.triangles.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+
+        let (_, _, i) = self.areas.sample(random::thread_rng().gen());
+        let v = self.triangles[i].positions.map(|j| self.positions[j]);
+        triangle::random_point(v) - origin
+    }
+}
+
+/// An error encountered while loading a mesh from a file.
+#[derive(Debug)]
+pub enum MeshError {
+    Io(io::Error),
+    /// The file is malformed, as found on the given (1-based) line.
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The file is malformed somewhere that doesn't have a line
+    /// number, like the data section of a binary file.
+    Format(String),
+    /// A file referenced by the mesh, like a material library, failed to load.
+    Referenced {
+        path: PathBuf,
+        error: Box<MeshError>,
+    },
+}
+
+impl MeshError {
+    pub(crate) fn parse(line: usize, message: impl Into<String>) -> Self {
+        Self::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::Io(e) => write!(f, "{e}"),
+            MeshError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            MeshError::Format(message) => write!(f, "{message}"),
+            MeshError::Referenced { path, error } => write!(f, "in {}: {error}", path.display()),
+        }
+    }
+}
+
+impl Error for MeshError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MeshError::Io(e) => Some(e),
+            MeshError::Parse { .. } | MeshError::Format(_) => None,
+            MeshError::Referenced { error, .. } => Some(&**error),
+        }
+    }
+}
+
+impl From<io::Error> for MeshError {
+    fn from(e: io::Error) -> Self {
+        MeshError::Io(e)
+    }
+}
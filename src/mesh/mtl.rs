@@ -0,0 +1,172 @@
+//! Loads Wavefront MTL material libraries, which OBJ files
+//! refer to with `mtllib` and pick from with `usemtl`.
+//!
+//! The MTL format describes Phong-style materials, which don't
+//! map exactly onto the ones in this crate, so each one gets
+//! converted to whichever material best matches its look.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::MeshError;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Metal, Scatter};
+use crate::vec::Color;
+
+/// The parameters of one `newmtl` entry.
+#[derive(Clone, Debug)]
+pub struct MtlMaterial {
+    pub name: String,
+    /// Diffuse color, `Kd`.
+    pub diffuse: Color,
+    /// Specular color, `Ks`.
+    pub specular: Color,
+    /// Emitted color, `Ke`.
+    pub emission: Color,
+    /// Specular exponent, `Ns`, from 0 to 1000.
+    pub shininess: f64,
+    /// Index of refraction, `Ni`.
+    pub index_of_refraction: f64,
+    /// Opacity, `d`, or one minus `Tr`.
+    pub dissolve: f64,
+    /// Illumination model, `illum`.
+    pub illum: u32,
+}
+
+impl MtlMaterial {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::new(0.0, 0.0, 0.0),
+            emission: Color::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            index_of_refraction: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+
+    /// Picks the material that best matches these parameters.
+    pub fn to_scatter(&self) -> Arc<dyn Scatter> {
+        // illumination models 4, 6, 7 and 9 are the transparent ones
+        let transparent = matches!(self.illum, 4 | 6 | 7 | 9) || self.dissolve < 1.0;
+        // models 3 and 5 add ray traced reflections
+        let reflective = matches!(self.illum, 3 | 5)
+            || max_component(self.specular) > max_component(self.diffuse);
+
+        if max_component(self.emission) > 0.0 {
+            Arc::new(DiffuseLight::new(self.emission))
+        } else if transparent {
+            Arc::new(Dielectric::new(self.index_of_refraction.max(1.0)))
+        } else if reflective {
+            // convert the Phong exponent into a roughness, which works well enough as a fuzz
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt().min(1.0);
+            Arc::new(Metal::new(self.specular, fuzz))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse))
+        }
+    }
+}
+
+fn max_component(c: Color) -> f64 {
+    c.x().max(c.y()).max(c.z())
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<HashMap<String, MtlMaterial>, MeshError> {
+    let file = File::open(path)?;
+    parse(BufReader::new(file))
+}
+
+pub fn parse<R: BufRead>(reader: R) -> Result<HashMap<String, MtlMaterial>, MeshError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<MtlMaterial> = None;
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = i + 1;
+        let error = |message: String| MeshError::parse(line_number, message);
+
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => &line,
+        };
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(error(String::from("`newmtl` needs a name")));
+            }
+            if let Some(done) = current.replace(MtlMaterial::new(args.join(" "))) {
+                materials.insert(done.name.clone(), done);
+            }
+            continue;
+        }
+
+        let mat = match current.as_mut() {
+            Some(mat) => mat,
+            None => return Err(error(format!("`{keyword}` before any `newmtl`"))),
+        };
+
+        let number = |arg: &str| -> Result<f64, MeshError> {
+            arg.parse()
+                .map_err(|_| error(format!("invalid number `{arg}`")))
+        };
+        let single = || -> Result<f64, MeshError> {
+            match args[..] {
+                [arg] => number(arg),
+                _ => Err(error(format!(
+                    "`{keyword}` needs 1 number, found {}",
+                    args.len()
+                ))),
+            }
+        };
+        let color = || -> Result<Color, MeshError> {
+            match args[..] {
+                // a single value means a shade of gray
+                [v] => {
+                    let v = number(v)?;
+                    Ok(Color::new(v, v, v))
+                }
+                [r, g, b] => Ok(Color::new(number(r)?, number(g)?, number(b)?)),
+                _ => Err(error(format!(
+                    "`{keyword}` needs an RGB color, found {} values",
+                    args.len()
+                ))),
+            }
+        };
+
+        match keyword {
+            "Kd" => mat.diffuse = color()?,
+            "Ks" => mat.specular = color()?,
+            "Ke" => mat.emission = color()?,
+            "Ns" => mat.shininess = single()?,
+            "Ni" => mat.index_of_refraction = single()?,
+            "d" => mat.dissolve = single()?,
+            "Tr" => mat.dissolve = 1.0 - single()?,
+            "illum" => {
+                mat.illum = match args[..] {
+                    [arg] => arg
+                        .parse()
+                        .map_err(|_| error(format!("invalid illumination model `{arg}`")))?,
+                    _ => return Err(error(String::from("`illum` needs 1 number"))),
+                }
+            }
+            // texture maps and the rest aren't supported
+            _ => {}
+        }
+    }
+
+    if let Some(done) = current {
+        materials.insert(done.name.clone(), done);
+    }
+
+    Ok(materials)
+}
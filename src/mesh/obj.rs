@@ -0,0 +1,395 @@
+//! Loads meshes from Wavefront OBJ files.
+//!
+//! Supports vertex positions, normals and texture coordinates,
+//! polygonal faces (which get split into a fan of triangles),
+//! groups, and materials from MTL libraries. Other statements,
+//! like lines and free-form geometry, are skipped.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::mtl::{self, MtlMaterial};
+use super::{MeshError, MeshTriangle, TriangleMesh};
+use crate::material::Scatter;
+use crate::vec::{Point3, Vec3};
+
+/// Loads an OBJ file, along with any material libraries it refers to,
+/// which are looked up relative to the OBJ file. Faces without a
+/// material, or whose material can't be found, get `default_mat`.
+pub fn load<P: AsRef<Path>>(
+    path: P,
+    default_mat: Arc<dyn Scatter>,
+) -> Result<TriangleMesh, MeshError> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    parse_from(BufReader::new(file), default_mat, Some(dir))
+}
+
+/// Parses an OBJ file from a reader. Since there's no file to find them
+/// relative to, material libraries are skipped and every face gets `mat`.
+pub fn parse<R: BufRead>(reader: R, mat: Arc<dyn Scatter>) -> Result<TriangleMesh, MeshError> {
+    parse_from(reader, mat, None)
+}
+
+fn parse_from<R: BufRead>(
+    reader: R,
+    default_mat: Arc<dyn Scatter>,
+    dir: Option<PathBuf>,
+) -> Result<TriangleMesh, MeshError> {
+    let mut parser = Parser::new(default_mat, dir);
+    for (i, line) in reader.lines().enumerate() {
+        parser.line = i + 1;
+        parser.parse_line(&line?)?;
+    }
+
+    Ok(TriangleMesh::new(
+        parser.positions,
+        parser.normals,
+        parser.uvs,
+        parser.triangles,
+        parser.materials[0].clone(),
+    )
+    .with_groups(parser.groups)
+    .with_materials(parser.materials))
+}
+
+/// The indices of one corner of a face, already resolved
+/// to start at zero.
+#[derive(Clone, Copy)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+struct Parser {
+    /// The line currently being parsed.
+    line: usize,
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    triangles: Vec<MeshTriangle>,
+    groups: Vec<String>,
+    group: usize,
+    /// Where to look for material libraries, if anywhere.
+    dir: Option<PathBuf>,
+    /// Every material defined by the libraries loaded so far.
+    library: HashMap<String, MtlMaterial>,
+    /// The materials in use, starting with the default one.
+    materials: Vec<Arc<dyn Scatter>>,
+    material_indices: HashMap<String, usize>,
+    material: usize,
+}
+
+impl Parser {
+    fn new(default_mat: Arc<dyn Scatter>, dir: Option<PathBuf>) -> Self {
+        Self {
+            line: 0,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            triangles: Vec::new(),
+            groups: vec![String::from("default")],
+            group: 0,
+            dir,
+            library: HashMap::new(),
+            materials: vec![default_mat],
+            material_indices: HashMap::new(),
+            material: 0,
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), MeshError> {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => return Ok(()),
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                // allow for either a `w` component or a vertex color after the position
+                let v = self.floats(&args, 3, 6)?;
+                self.positions.push(Point3::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let n = self.floats(&args, 3, 3)?;
+                self.normals.push(Vec3::new(n[0], n[1], n[2]));
+            }
+            "vt" => {
+                let uv = self.floats(&args, 1, 3)?;
+                self.uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            }
+            "f" => self.face(&args)?,
+            "g" | "o" => {
+                let name = if args.is_empty() {
+                    String::from("default")
+                } else {
+                    args.join(" ")
+                };
+                self.group = match self.groups.iter().position(|g| *g == name) {
+                    Some(group) => group,
+                    None => {
+                        self.groups.push(name);
+                        self.groups.len() - 1
+                    }
+                };
+            }
+            "mtllib" => {
+                if let Some(dir) = &self.dir {
+                    // library names can't contain spaces, so there may be several
+                    for name in &args {
+                        let path = dir.join(name);
+                        let library = mtl::load(&path).map_err(|error| MeshError::Referenced {
+                            path,
+                            error: Box::new(error),
+                        })?;
+                        self.library.extend(library);
+                    }
+                }
+            }
+            "usemtl" => self.material = self.material_index(&args.join(" ")),
+            // everything else is either irrelevant to rendering or unsupported
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn face(&mut self, args: &[&str]) -> Result<(), MeshError> {
+        if args.len() < 3 {
+            return Err(self.error(format!(
+                "face needs at least 3 vertices, found {}",
+                args.len()
+            )));
+        }
+
+        let vertices = args
+            .iter()
+            .map(|arg| self.face_vertex(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // split the polygon into a fan of triangles around its first vertex
+        for i in 1..vertices.len() - 1 {
+            let corners = [vertices[0], vertices[i], vertices[i + 1]];
+            let normals = match corners.map(|c| c.normal) {
+                [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+                _ => None,
+            };
+            let uvs = match corners.map(|c| c.uv) {
+                [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+                _ => None,
+            };
+
+            self.triangles.push(MeshTriangle {
+                positions: corners.map(|c| c.position),
+                normals,
+                uvs,
+                group: self.group,
+                material: self.material,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Finds the index of the named material, converting it from
+    /// the library the first time it's used. Falls back to the
+    /// default material if no library defines it yet, without
+    /// remembering that, since a later `mtllib` may define it.
+    fn material_index(&mut self, name: &str) -> usize {
+        if let Some(&index) = self.material_indices.get(name) {
+            return index;
+        }
+
+        let mtl = match self.library.get(name) {
+            Some(mtl) => mtl,
+            None => return 0,
+        };
+        self.materials.push(mtl.to_scatter());
+        let index = self.materials.len() - 1;
+        self.material_indices.insert(name.to_string(), index);
+
+        index
+    }
+
+    /// Parses one corner of a face, in one of the forms
+    /// `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+    fn face_vertex(&self, arg: &str) -> Result<FaceVertex, MeshError> {
+        let mut parts = arg.split('/');
+        let position = self.index(parts.next().unwrap_or(""), self.positions.len(), "vertex")?;
+        let uv = match parts.next() {
+            Some("") | None => None,
+            Some(index) => Some(self.index(index, self.uvs.len(), "texture coordinate")?),
+        };
+        let normal = match parts.next() {
+            Some("") | None => None,
+            Some(index) => Some(self.index(index, self.normals.len(), "normal")?),
+        };
+
+        if parts.next().is_some() {
+            return Err(self.error(format!("invalid face vertex `{arg}`")));
+        }
+
+        Ok(FaceVertex {
+            position,
+            uv,
+            normal,
+        })
+    }
+
+    /// Resolves a 1-based index, or a negative one counting back from
+    /// the most recent element, into a 0-based index.
+    fn index(&self, token: &str, len: usize, kind: &str) -> Result<usize, MeshError> {
+        let index: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {kind} index `{token}`")))?;
+
+        let resolved = if index > 0 {
+            index - 1
+        } else {
+            len as i64 + index
+        };
+        if index == 0 || resolved < 0 || resolved >= len as i64 {
+            return Err(self.error(format!(
+                "{kind} index {index} is out of range, only {len} defined so far"
+            )));
+        }
+
+        Ok(resolved as usize)
+    }
+
+    fn floats(&self, args: &[&str], min: usize, max: usize) -> Result<Vec<f64>, MeshError> {
+        if args.len() < min || args.len() > max {
+            let expected = if min == max {
+                format!("{min}")
+            } else {
+                format!("{min} to {max}")
+            };
+            return Err(self.error(format!("expected {expected} numbers, found {}", args.len())));
+        }
+
+        args.iter()
+            .map(|arg| {
+                arg.parse()
+                    .map_err(|_| self.error(format!("invalid number `{arg}`")))
+            })
+            .collect()
+    }
+
+    fn error(&self, message: String) -> MeshError {
+        MeshError::parse(self.line, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::hit::Hit;
+    use crate::material::Lambertian;
+    use crate::ray::Ray;
+    use crate::vec::Color;
+
+    fn mat() -> Arc<dyn Scatter> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn parses_vertex_data_and_faces() {
+        let obj = "\
+# a unit square in the XY plane, facing +Z
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+g square
+f 1/1/1 2/2/1 3/3/1 4/4/1
+";
+        let mesh = parse(Cursor::new(obj), mat()).unwrap();
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(mesh.groups(), ["default", "square"]);
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.uvs, [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        assert!(mesh.triangles.iter().all(|tri| tri.group == 1
+            && tri.normals == Some([0, 0, 0])
+            && tri.uvs == Some(tri.positions)));
+
+        let r = Ray::new(Point3::new(0.25, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = mesh.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-12);
+        assert!((rec.u - 0.25).abs() < 1e-12 && (rec.v - 0.75).abs() < 1e-12);
+        assert!((rec.shading_normal.z() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn resolves_negative_indices() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n";
+        let mesh = parse(Cursor::new(obj), mat()).unwrap();
+        assert_eq!(mesh.triangles[0].positions, [0, 1, 2]);
+        assert_eq!(mesh.triangles[0].normals, None);
+    }
+
+    #[test]
+    fn finds_materials_defined_after_their_first_use() {
+        let dir = std::env::temp_dir().join(format!("raytracing-obj-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("red.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        let obj = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+usemtl red
+f 1 2 3
+mtllib red.mtl
+usemtl red
+f 1 2 3
+usemtl blue
+f 1 2 3
+";
+        let mesh = parse_from(Cursor::new(obj), mat(), Some(dir.clone()));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // red isn't defined yet for the first face, and blue never is
+        let mesh = mesh.unwrap();
+        let mut materials: Vec<usize> = mesh.triangles.iter().map(|tri| tri.material).collect();
+        materials.sort_unstable();
+        assert_eq!(materials, [0, 0, 1]);
+        assert_eq!(mesh.materials.len(), 2);
+    }
+
+    #[test]
+    fn rejects_bad_faces_with_their_line() {
+        let cases = [
+            ("v 0 0 0\nv 1 0 0\nf 1 2\n", 3),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", 4),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 0\n", 4),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3/1\n", 4),
+            ("v 0 0 0\nv 1 0 x\n", 2),
+        ];
+        for (obj, expected) in cases {
+            match parse(Cursor::new(obj), mat()) {
+                Err(MeshError::Parse { line, .. }) => assert_eq!(line, expected, "{obj:?}"),
+                Err(e) => panic!("wrong error for {obj:?}: {e}"),
+                Ok(_) => panic!("{obj:?} parsed"),
+            }
+        }
+    }
+}
@@ -0,0 +1,666 @@
+//! Loads meshes from PLY (Stanford polygon) files, in
+//! either the ASCII or one of the binary encodings.
+//!
+//! Reads vertex positions, along with normals, texture
+//! coordinates and colors when the vertices have them,
+//! and polygonal faces, which get split into a fan of
+//! triangles. Any other elements are skipped.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{MeshError, MeshTriangle, TriangleMesh};
+use crate::material::Scatter;
+use crate::vec::{Color, Point3, Vec3};
+
+pub fn load<P: AsRef<Path>>(path: P, mat: Arc<dyn Scatter>) -> Result<TriangleMesh, MeshError> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+    parse_from(BufReader::new(file), mat, Some(size))
+}
+
+pub fn parse<R: BufRead>(reader: R, mat: Arc<dyn Scatter>) -> Result<TriangleMesh, MeshError> {
+    parse_from(reader, mat, None)
+}
+
+/// Parses a mesh from a reader, given the size of the whole
+/// file if it's known, to catch lists longer than the file.
+fn parse_from<R: BufRead>(
+    mut reader: R,
+    mat: Arc<dyn Scatter>,
+    size: Option<u64>,
+) -> Result<TriangleMesh, MeshError> {
+    let header = Header::parse(&mut reader)?;
+    let mut body = Body {
+        reader,
+        format: header.format,
+        line: header.lines,
+        tokens: Vec::new(),
+        remaining: size.map(|size| size.saturating_sub(header.bytes)),
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colors = Vec::new();
+    let mut faces: Vec<Vec<i64>> = Vec::new();
+
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => {
+                let attributes = VertexAttributes::find(element);
+                let x = attributes.position.ok_or_else(|| {
+                    MeshError::Format(String::from("vertices don't have x, y and z properties"))
+                })?;
+
+                let mut values = vec![0.0; element.properties.len()];
+                for _ in 0..element.count {
+                    body.start_row()?;
+                    for (value, property) in values.iter_mut().zip(&element.properties) {
+                        *value = match property.kind {
+                            PropertyKind::Scalar(ty) => body.read(ty)?,
+                            PropertyKind::List { .. } => {
+                                body.skip_list(&property.kind)?;
+                                0.0
+                            }
+                        };
+                    }
+
+                    positions.push(Point3::new(values[x[0]], values[x[1]], values[x[2]]));
+                    if let Some(n) = attributes.normal {
+                        normals.push(Vec3::new(values[n[0]], values[n[1]], values[n[2]]));
+                    }
+                    if let Some(uv) = attributes.uv {
+                        uvs.push((values[uv[0]], values[uv[1]]));
+                    }
+                    if let Some(c) = attributes.color {
+                        let scale = |i: usize| {
+                            // integer colors go from 0 to the type's maximum
+                            match element.properties[i].kind {
+                                PropertyKind::Scalar(ty) => values[i] / ty.color_max(),
+                                PropertyKind::List { .. } => 0.0,
+                            }
+                        };
+                        colors.push(Color::new(scale(c[0]), scale(c[1]), scale(c[2])));
+                    }
+                }
+            }
+            "face" => {
+                let indices = element
+                    .properties
+                    .iter()
+                    .position(|p| p.name == "vertex_indices" || p.name == "vertex_index")
+                    .ok_or_else(|| {
+                        MeshError::Format(String::from("faces don't have a vertex_indices list"))
+                    })?;
+
+                for _ in 0..element.count {
+                    body.start_row()?;
+                    for (i, property) in element.properties.iter().enumerate() {
+                        match property.kind {
+                            PropertyKind::List { count, item } if i == indices => {
+                                let n = body.read_list_len(count, item)?;
+                                let face = (0..n)
+                                    .map(|_| body.read(item).map(|index| index as i64))
+                                    .collect::<Result<Vec<_>, _>>()?;
+                                faces.push(face);
+                            }
+                            PropertyKind::List { .. } => body.skip_list(&property.kind)?,
+                            PropertyKind::Scalar(ty) => {
+                                body.read(ty)?;
+                            }
+                        }
+                    }
+                }
+            }
+            _ => body.skip_element(element)?,
+        }
+    }
+
+    let mut triangles = Vec::new();
+    for face in faces {
+        if face.len() < 3 {
+            return Err(MeshError::Format(format!(
+                "face needs at least 3 vertices, found {}",
+                face.len()
+            )));
+        }
+        if let Some(&index) = face
+            .iter()
+            .find(|&&index| index < 0 || index >= positions.len() as i64)
+        {
+            return Err(MeshError::Format(format!(
+                "vertex index {index} is out of range, only {} vertices",
+                positions.len()
+            )));
+        }
+
+        // split the polygon into a fan of triangles around its first vertex
+        for i in 1..face.len() - 1 {
+            let corners = [face[0], face[i], face[i + 1]].map(|index| index as usize);
+            triangles.push(MeshTriangle {
+                positions: corners,
+                normals: (!normals.is_empty()).then_some(corners),
+                uvs: (!uvs.is_empty()).then_some(corners),
+                group: 0,
+                material: 0,
+            });
+        }
+    }
+
+    let mesh = TriangleMesh::new(positions, normals, uvs, triangles, mat);
+    if colors.is_empty() {
+        Ok(mesh)
+    } else {
+        Ok(mesh.with_colors(colors))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+
+    /// The value of full intensity for a color channel of this type.
+    fn color_max(self) -> f64 {
+        match self {
+            ScalarType::I8 => i8::MAX as f64,
+            ScalarType::U8 => u8::MAX as f64,
+            ScalarType::I16 => i16::MAX as f64,
+            ScalarType::U16 => u16::MAX as f64,
+            ScalarType::I32 => i32::MAX as f64,
+            ScalarType::U32 => u32::MAX as f64,
+            ScalarType::F32 | ScalarType::F64 => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum PropertyKind {
+    Scalar(ScalarType),
+    /// A list of `item`s, prefixed by its length.
+    List {
+        count: ScalarType,
+        item: ScalarType,
+    },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Where to find each vertex attribute among an element's properties.
+struct VertexAttributes {
+    position: Option<[usize; 3]>,
+    normal: Option<[usize; 3]>,
+    uv: Option<[usize; 2]>,
+    color: Option<[usize; 3]>,
+}
+
+impl VertexAttributes {
+    fn find(element: &Element) -> Self {
+        let find = |names: &[&str]| {
+            element.properties.iter().position(|p| {
+                matches!(p.kind, PropertyKind::Scalar(_)) && names.contains(&p.name.as_str())
+            })
+        };
+
+        let position = (|| Some([find(&["x"])?, find(&["y"])?, find(&["z"])?]))();
+        let normal = (|| Some([find(&["nx"])?, find(&["ny"])?, find(&["nz"])?]))();
+        let uv = (|| {
+            Some([
+                find(&["u", "s", "texture_u", "texture_s"])?,
+                find(&["v", "t", "texture_v", "texture_t"])?,
+            ])
+        })();
+        let color = (|| {
+            Some([
+                find(&["red", "r", "diffuse_red"])?,
+                find(&["green", "g", "diffuse_green"])?,
+                find(&["blue", "b", "diffuse_blue"])?,
+            ])
+        })();
+
+        Self {
+            position,
+            normal,
+            uv,
+            color,
+        }
+    }
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    /// How many lines the header took up.
+    lines: usize,
+    /// How many bytes the header took up.
+    bytes: u64,
+}
+
+impl Header {
+    fn parse<R: BufRead>(reader: &mut R) -> Result<Self, MeshError> {
+        let mut format = None;
+        let mut elements: Vec<Element> = Vec::new();
+        let mut line = 0;
+        let mut bytes = 0;
+
+        loop {
+            let mut text = String::new();
+            let read = reader.read_line(&mut text)?;
+            if read == 0 {
+                return Err(MeshError::parse(line, "file ended before `end_header`"));
+            }
+            line += 1;
+            bytes += read as u64;
+
+            let error = |message: String| MeshError::parse(line, message);
+            let tokens: Vec<&str> = text.split_whitespace().collect();
+            if line == 1 {
+                if tokens != ["ply"] {
+                    return Err(error(String::from("not a PLY file, missing `ply` magic")));
+                }
+                continue;
+            }
+
+            match tokens[..] {
+                ["format", name, _version] => {
+                    format = Some(match name {
+                        "ascii" => Format::Ascii,
+                        "binary_little_endian" => Format::BinaryLittleEndian,
+                        "binary_big_endian" => Format::BinaryBigEndian,
+                        _ => return Err(error(format!("unknown format `{name}`"))),
+                    });
+                }
+                ["element", name, count] => elements.push(Element {
+                    name: name.to_string(),
+                    count: count
+                        .parse()
+                        .map_err(|_| error(format!("invalid element count `{count}`")))?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", count, item, name] => {
+                    let kind = PropertyKind::List {
+                        count: Self::scalar_type(count, line)?,
+                        item: Self::scalar_type(item, line)?,
+                    };
+                    Self::add_property(&mut elements, name, kind, line)?;
+                }
+                ["property", ty, name] => {
+                    let kind = PropertyKind::Scalar(Self::scalar_type(ty, line)?);
+                    Self::add_property(&mut elements, name, kind, line)?;
+                }
+                ["end_header"] => break,
+                ["comment", ..] | ["obj_info", ..] | [] => {}
+                _ => return Err(error(format!("unexpected header line `{}`", text.trim()))),
+            }
+        }
+
+        let format = format.ok_or_else(|| MeshError::parse(line, "header has no `format`"))?;
+        Ok(Self {
+            format,
+            elements,
+            lines: line,
+            bytes,
+        })
+    }
+
+    fn scalar_type(name: &str, line: usize) -> Result<ScalarType, MeshError> {
+        ScalarType::parse(name)
+            .ok_or_else(|| MeshError::parse(line, format!("unknown property type `{name}`")))
+    }
+
+    fn add_property(
+        elements: &mut [Element],
+        name: &str,
+        kind: PropertyKind,
+        line: usize,
+    ) -> Result<(), MeshError> {
+        let element = elements
+            .last_mut()
+            .ok_or_else(|| MeshError::parse(line, "property before any element"))?;
+        element.properties.push(Property {
+            name: name.to_string(),
+            kind,
+        });
+
+        Ok(())
+    }
+}
+
+/// Reads values from the data following the header. ASCII
+/// files have one element per line, while binary ones
+/// are just the values packed back to back.
+struct Body<R> {
+    reader: R,
+    format: Format,
+    /// The line last read, for ASCII files.
+    line: usize,
+    /// The values of the current line not read yet, in reverse order.
+    tokens: Vec<String>,
+    /// How many bytes are left to read, for binary files, if known.
+    remaining: Option<u64>,
+}
+
+impl<R: BufRead> Body<R> {
+    /// Moves on to the next element. For ASCII files, that
+    /// means reading the next line.
+    fn start_row(&mut self) -> Result<(), MeshError> {
+        if self.format != Format::Ascii {
+            return Ok(());
+        }
+
+        if let Some(token) = self.tokens.last() {
+            return Err(MeshError::parse(
+                self.line,
+                format!("unexpected value `{token}` after the end of an element"),
+            ));
+        }
+
+        let mut text = String::new();
+        if self.reader.read_line(&mut text)? == 0 {
+            return Err(MeshError::parse(
+                self.line,
+                "file ended before all elements were read",
+            ));
+        }
+        self.line += 1;
+        self.tokens = text.split_whitespace().rev().map(String::from).collect();
+
+        Ok(())
+    }
+
+    fn read(&mut self, ty: ScalarType) -> Result<f64, MeshError> {
+        let (little_endian, mut buf) = match self.format {
+            Format::Ascii => {
+                let token = self.tokens.pop().ok_or_else(|| {
+                    MeshError::parse(self.line, "element has fewer values than properties")
+                })?;
+                return token
+                    .parse()
+                    .map_err(|_| MeshError::parse(self.line, format!("invalid number `{token}`")));
+            }
+            Format::BinaryLittleEndian => (true, [0; 8]),
+            Format::BinaryBigEndian => (false, [0; 8]),
+        };
+
+        let size = ty.size();
+        self.reader.read_exact(&mut buf[..size]).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                MeshError::Format(String::from("file ended before all elements were read"))
+            } else {
+                MeshError::Io(e)
+            }
+        })?;
+        if !little_endian {
+            buf[..size].reverse();
+        }
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.saturating_sub(size as u64);
+        }
+
+        Ok(match ty {
+            ScalarType::I8 => buf[0] as i8 as f64,
+            ScalarType::U8 => buf[0] as f64,
+            ScalarType::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            ScalarType::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            ScalarType::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::F64 => f64::from_le_bytes(buf),
+        })
+    }
+
+    /// Reads the length of a list, making sure the rest of the file
+    /// has room for that many items before anything relies on it.
+    fn read_list_len(&mut self, count: ScalarType, item: ScalarType) -> Result<usize, MeshError> {
+        let n = self.read(count)?;
+        let room = match self.format {
+            Format::Ascii => Some(self.tokens.len() as u64),
+            _ => self
+                .remaining
+                .map(|remaining| remaining / item.size() as u64),
+        };
+
+        if n < 0.0 || n.fract() != 0.0 || room.is_some_and(|room| n > room as f64) {
+            let message = format!("a list of {n} items doesn't fit in the file");
+            return Err(match self.format {
+                Format::Ascii => MeshError::parse(self.line, message),
+                _ => MeshError::Format(message),
+            });
+        }
+
+        Ok(n as usize)
+    }
+
+    fn skip_list(&mut self, kind: &PropertyKind) -> Result<(), MeshError> {
+        if let PropertyKind::List { count, item } = *kind {
+            let n = self.read_list_len(count, item)?;
+            for _ in 0..n {
+                self.read(item)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn skip_element(&mut self, element: &Element) -> Result<(), MeshError> {
+        for _ in 0..element.count {
+            self.start_row()?;
+            for property in &element.properties {
+                match property.kind {
+                    PropertyKind::Scalar(ty) => {
+                        self.read(ty)?;
+                    }
+                    PropertyKind::List { .. } => self.skip_list(&property.kind)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::material::Lambertian;
+
+    fn mat() -> Arc<dyn Scatter> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    const HEADER: &str = "\
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    /// A unit square in the XY plane, facing +Z, with a red corner.
+    const VERTICES: [[f32; 6]; 4] = [
+        [0.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+        [1.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+        [1.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+    ];
+    const COLORS: [[u8; 3]; 4] = [
+        [255, 0, 0],
+        [255, 255, 255],
+        [255, 255, 255],
+        [255, 255, 255],
+    ];
+
+    fn ascii() -> Vec<u8> {
+        let mut text = format!("ply\nformat ascii 1.0\ncomment a square\n{HEADER}");
+        for (v, c) in VERTICES.iter().zip(COLORS) {
+            let v: Vec<String> = v.iter().map(f32::to_string).collect();
+            text += &format!("{} {} {} {}\n", v.join(" "), c[0], c[1], c[2]);
+        }
+        text += "4 0 1 2 3\n";
+        text.into_bytes()
+    }
+
+    fn binary(little_endian: bool) -> Vec<u8> {
+        let name = if little_endian {
+            "binary_little_endian"
+        } else {
+            "binary_big_endian"
+        };
+        let mut data = format!("ply\nformat {name} 1.0\n{HEADER}").into_bytes();
+        for (v, c) in VERTICES.iter().zip(COLORS) {
+            for x in v {
+                data.extend(if little_endian {
+                    x.to_le_bytes()
+                } else {
+                    x.to_be_bytes()
+                });
+            }
+            data.extend(c);
+        }
+        data.push(4);
+        for i in 0..4i32 {
+            data.extend(if little_endian {
+                i.to_le_bytes()
+            } else {
+                i.to_be_bytes()
+            });
+        }
+        data
+    }
+
+    fn assert_square(data: &[u8]) {
+        let mesh = parse_from(Cursor::new(data), mat(), Some(data.len() as u64)).unwrap();
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(mesh.positions.len(), 4);
+        for (p, v) in mesh.positions.iter().zip(VERTICES) {
+            assert_eq!([p.x(), p.y(), p.z()], [v[0], v[1], v[2]].map(f64::from));
+        }
+        assert!(mesh.normals.iter().all(|n| n.z() == 1.0));
+        assert_eq!(mesh.colors[0].x(), 1.0);
+        assert_eq!(mesh.colors[0].y(), 0.0);
+        assert_eq!(mesh.colors[1].y(), 1.0);
+        assert!(mesh
+            .triangles
+            .iter()
+            .all(|tri| tri.normals == Some(tri.positions)));
+    }
+
+    #[test]
+    fn reads_every_encoding_alike() {
+        assert_square(&ascii());
+        assert_square(&binary(true));
+        assert_square(&binary(false));
+    }
+
+    #[test]
+    fn rejects_lists_longer_than_the_file() {
+        // the face claims 200 indices, but the file ends after 4
+        let mut data = binary(true);
+        let count = data.len() - 17;
+        data[count] = 200;
+        match parse_from(Cursor::new(&data), mat(), Some(data.len() as u64)) {
+            Err(MeshError::Format(message)) => assert!(message.contains("200"), "{message}"),
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("parsed"),
+        }
+
+        let data = String::from_utf8(ascii())
+            .unwrap()
+            .replace("4 0 1 2 3", "5 0 1 2 3");
+        match parse(Cursor::new(data), mat()) {
+            Err(MeshError::Parse { line, .. }) => assert_eq!(line, 21),
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("parsed"),
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let data = binary(false);
+        let data = &data[..data.len() - 2];
+        assert!(matches!(
+            parse(Cursor::new(data), mat()),
+            Err(MeshError::Format(_))
+        ));
+
+        let data = ascii();
+        let data = &data[..data.len() - 10];
+        assert!(matches!(
+            parse(Cursor::new(data), mat()),
+            Err(MeshError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let data = String::from_utf8(ascii())
+            .unwrap()
+            .replace("4 0 1 2 3", "3 0 1 4");
+        assert!(matches!(
+            parse(Cursor::new(data), mat()),
+            Err(MeshError::Format(_))
+        ));
+    }
+}
@@ -0,0 +1,300 @@
+//! Loads meshes from STL files, as exported by CAD tools,
+//! in either the ASCII or the binary encoding.
+//!
+//! STL stores every triangle on its own, with a normal for
+//! the whole facet. Identical vertices are merged so they're
+//! only stored once, and the facet normals are used for flat
+//! shading, since there are no per-vertex normals.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{MeshError, MeshTriangle, TriangleMesh};
+use crate::material::Scatter;
+use crate::vec::{Point3, Vec3};
+
+/// Size of the binary header, which is followed by the triangle count.
+const HEADER_SIZE: usize = 80;
+/// Size of one triangle in a binary file: a normal, three
+/// vertices, and an unused attribute byte count.
+const TRIANGLE_SIZE: usize = 50;
+
+pub fn load<P: AsRef<Path>>(path: P, mat: Arc<dyn Scatter>) -> Result<TriangleMesh, MeshError> {
+    let file = File::open(path)?;
+    parse(BufReader::new(file), mat)
+}
+
+pub fn parse<R: Read>(mut reader: R, mat: Arc<dyn Scatter>) -> Result<TriangleMesh, MeshError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let mut builder = Builder::default();
+    if is_binary(&data) {
+        parse_binary(&data, &mut builder)?;
+    } else {
+        parse_ascii(&data, &mut builder)?;
+    }
+
+    Ok(TriangleMesh::new(
+        builder.positions,
+        builder.normals,
+        Vec::new(),
+        builder.triangles,
+        mat,
+    ))
+}
+
+/// Binary files are supposed to not start with `solid`, but plenty of
+/// exporters put it in the header anyway. So unless the file size
+/// matches the triangle count in the binary header exactly, assume
+/// any text file starting with `solid` is ASCII.
+fn is_binary(data: &[u8]) -> bool {
+    if data.len() >= HEADER_SIZE + 4 {
+        let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+        if data.len() == HEADER_SIZE + 4 + count * TRIANGLE_SIZE {
+            return true;
+        }
+    }
+
+    let start = data
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(0);
+    !data[start..].starts_with(b"solid") || std::str::from_utf8(data).is_err()
+}
+
+fn parse_binary(data: &[u8], builder: &mut Builder) -> Result<(), MeshError> {
+    if data.len() < HEADER_SIZE + 4 {
+        return Err(MeshError::Format(String::from(
+            "file is too short to be a binary STL file",
+        )));
+    }
+
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    let expected = HEADER_SIZE + 4 + count * TRIANGLE_SIZE;
+    if data.len() < expected {
+        return Err(MeshError::Format(format!(
+            "header says there are {count} triangles, but the file ends after {}",
+            (data.len() - HEADER_SIZE - 4) / TRIANGLE_SIZE
+        )));
+    }
+
+    let read_vec = |offset: usize| {
+        let f = |i: usize| {
+            let at = offset + 4 * i;
+            f32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as f64
+        };
+        Vec3::new(f(0), f(1), f(2))
+    };
+
+    for i in 0..count {
+        let offset = HEADER_SIZE + 4 + i * TRIANGLE_SIZE;
+        builder.add_facet(
+            read_vec(offset),
+            [
+                read_vec(offset + 12),
+                read_vec(offset + 24),
+                read_vec(offset + 36),
+            ],
+        );
+    }
+
+    Ok(())
+}
+
+fn parse_ascii(data: &[u8], builder: &mut Builder) -> Result<(), MeshError> {
+    // already checked by `is_binary`
+    let text = std::str::from_utf8(data).unwrap();
+
+    let mut normal = Vec3::new(0.0, 0.0, 0.0);
+    let mut vertices = Vec::new();
+    let mut in_solid = false;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| MeshError::parse(line_number, message);
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        let vector = |args: &[&str]| -> Result<Vec3, MeshError> {
+            let number = |arg: &str| {
+                arg.parse::<f64>()
+                    .map_err(|_| error(format!("invalid number `{arg}`")))
+            };
+            match *args {
+                [x, y, z] => Ok(Vec3::new(number(x)?, number(y)?, number(z)?)),
+                _ => Err(error(format!("expected 3 numbers, found {}", args.len()))),
+            }
+        };
+
+        match tokens[..] {
+            [] => {}
+            ["solid", ..] => in_solid = true,
+            ["endsolid", ..] => in_solid = false,
+            _ if !in_solid => {
+                return Err(error(format!("`{}` outside of a solid", tokens[0])));
+            }
+            ["facet", "normal", ref args @ ..] => {
+                normal = vector(args)?;
+                vertices.clear();
+            }
+            ["outer", "loop"] | ["endloop"] => {}
+            ["vertex", ref args @ ..] => vertices.push(vector(args)?),
+            ["endfacet"] => {
+                if vertices.len() != 3 {
+                    return Err(error(format!(
+                        "facet needs 3 vertices, found {}",
+                        vertices.len()
+                    )));
+                }
+                builder.add_facet(normal, [vertices[0], vertices[1], vertices[2]]);
+            }
+            _ => return Err(error(format!("unexpected `{}`", line.trim()))),
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Builder {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    triangles: Vec<MeshTriangle>,
+    /// Index of every position added so far, keyed by its exact bits.
+    indices: HashMap<[u64; 3], usize>,
+}
+
+impl Builder {
+    fn add_facet(&mut self, normal: Vec3, vertices: [Point3; 3]) {
+        let positions = vertices.map(|v| {
+            let key = [v.x().to_bits(), v.y().to_bits(), v.z().to_bits()];
+            *self.indices.entry(key).or_insert_with(|| {
+                self.positions.push(v);
+                self.positions.len() - 1
+            })
+        });
+
+        // some exporters leave the normal zeroed, in which
+        // case the winding order has to do instead
+        let normals = if normal.near_zero() {
+            None
+        } else {
+            self.normals.push(normal.normalized());
+            let n = self.normals.len() - 1;
+            Some([n, n, n])
+        };
+
+        self.triangles.push(MeshTriangle {
+            positions,
+            normals,
+            uvs: None,
+            group: 0,
+            material: 0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::vec::Color;
+
+    fn mat() -> Arc<dyn Scatter> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    /// A unit square in the XY plane, facing +Z, as two facets
+    /// sharing an edge.
+    const FACETS: [[[f32; 3]; 4]; 2] = [
+        [
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ],
+        [
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ],
+    ];
+
+    fn ascii() -> Vec<u8> {
+        let mut text = String::from("solid square\n");
+        for [normal, vertices @ ..] in FACETS {
+            let [x, y, z] = normal;
+            text += &format!("  facet normal {x} {y} {z}\n    outer loop\n");
+            for [x, y, z] in vertices {
+                text += &format!("      vertex {x} {y} {z}\n");
+            }
+            text += "    endloop\n  endfacet\n";
+        }
+        text += "endsolid square\n";
+        text.into_bytes()
+    }
+
+    fn binary() -> Vec<u8> {
+        // exporters often start the header with `solid` too
+        let mut data = b"solid square".to_vec();
+        data.resize(HEADER_SIZE, 0);
+        data.extend((FACETS.len() as u32).to_le_bytes());
+        for facet in FACETS {
+            for x in facet.as_flattened() {
+                data.extend(x.to_le_bytes());
+            }
+            data.extend([0, 0]);
+        }
+        data
+    }
+
+    fn assert_square(data: &[u8]) {
+        let mesh = parse(Cursor::new(data), mat()).unwrap();
+        assert_eq!(mesh.triangle_count(), 2);
+        // the shared corners are only stored once
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.normals.len(), 2);
+        assert!(mesh.normals.iter().all(|n| n.z() == 1.0));
+        assert!(mesh.triangles.iter().all(|tri| tri.normals.is_some()));
+    }
+
+    #[test]
+    fn reads_both_encodings_alike() {
+        assert_square(&ascii());
+        assert_square(&binary());
+    }
+
+    #[test]
+    fn rejects_truncated_binary_files() {
+        let mut data = binary();
+        data[HEADER_SIZE] = 3;
+        match parse(Cursor::new(&data), mat()) {
+            Err(MeshError::Format(message)) => assert!(message.contains("3 triangles")),
+            Err(e) => panic!("wrong error: {e}"),
+            Ok(_) => panic!("parsed"),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_ascii_facets_with_their_line() {
+        let text = String::from_utf8(ascii()).unwrap();
+        let cases = [
+            (text.replacen("      vertex 1 1 0\n", "", 1), 7),
+            (text.replacen("vertex 1 0 0", "vertex 1 0", 1), 5),
+            (text.replacen("outer loop", "outer", 1), 3),
+        ];
+        for (stl, expected) in cases {
+            match parse(Cursor::new(&stl), mat()) {
+                Err(MeshError::Parse { line, .. }) => assert_eq!(line, expected, "{stl}"),
+                Err(e) => panic!("wrong error for {stl}: {e}"),
+                Ok(_) => panic!("{stl} parsed"),
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Provides an orthonormal basis, for turning directions
+//! sampled around the Z axis into directions around
+//! some other axis, like a surface normal.
+
+use super::vec::Vec3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    /// Creates a basis whose `w` axis points along `n`.
+    pub fn new(n: Vec3) -> Self {
+        let w = n.normalized();
+        // any axis works, as long as it isn't parallel to w
+        let a = if w.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).normalized();
+        let u = w.cross(v);
+
+        Self { u, v, w }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    /// Converts a direction given in this basis to world space.
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    /// Converts a world space direction to this basis, undoing `local`.
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
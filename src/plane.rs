@@ -0,0 +1,64 @@
+//! Represents an infinite plane with a dynamic material.
+//!
+//! A plane has no bounding box, so it's tested against every
+//! ray instead of going into a tree with the other objects.
+
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::onb::Onb;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+pub struct Plane {
+    point: Point3,
+    /// Axes along the plane for its texture coordinates, with the normal as `w`.
+    axes: Onb,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Plane {
+    /// Creates the plane through `point` whose front face points along
+    /// `normal`, or returns none if the normal is zero.
+    pub fn new(point: Point3, normal: Vec3, mat: Arc<dyn Scatter>) -> Option<Self> {
+        if normal.length() == 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            point,
+            axes: Onb::new(normal),
+            mat,
+        })
+    }
+}
+
+impl Hit for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let normal = self.axes.w();
+        let denom = normal.dot(r.direction());
+        if denom.abs() < 1e-12 {
+            // the ray is parallel to the plane
+            return None;
+        }
+
+        let t = normal.dot(self.point - r.origin()) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // the texture coordinates are distances along the plane,
+        // so a repeating texture repeats once every unit
+        let mut rec = HitRecord::new(r, t, normal, &*self.mat);
+        let offset = rec.p - self.point;
+        (rec.u, rec.v) = (offset.dot(self.axes.u()), offset.dot(self.axes.v()));
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
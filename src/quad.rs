@@ -0,0 +1,113 @@
+//! Represents a quadrilateral with a dynamic material.
+//!
+//! A quad is a parallelogram, given by one corner and the two
+//! edges leaving it. Rectangles lined up with the axes, like the
+//! walls of a room, are the most common kind.
+
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::random;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    mat: Arc<dyn Scatter>,
+    normal: Vec3,
+    /// Turns a point's offset from `q` into its coordinates along
+    /// `u` and `v`, as the normal of the plane over its length squared.
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+    /// Creates a quad with a corner at `q`, spanning the edges `u` and `v`.
+    /// Its front face is the one that `u` turns counter-clockwise to `v` on.
+    /// Returns none if the edges are zero or parallel.
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Arc<dyn Scatter>) -> Option<Self> {
+        let n = u.cross(v);
+        if n.length() == 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            q,
+            u,
+            v,
+            mat,
+            normal: n.normalized(),
+            w: n / n.length_squared(),
+            area: n.length(),
+        })
+    }
+
+    /// Finds where the ray meets the quad's plane, returning the
+    /// distance along the ray and the point's coordinates along the
+    /// edges, which are between 0 and 1 inside the quad.
+    fn intersect(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+        let denom = self.normal.dot(r.direction());
+        if denom.abs() < 1e-12 {
+            // the ray is parallel to the quad
+            return None;
+        }
+
+        let t = self.normal.dot(self.q - r.origin()) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let offset = r.at(t) - self.q;
+        let alpha = self.w.dot(offset.cross(self.v));
+        let beta = self.w.dot(self.u.cross(offset));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some((t, alpha, beta))
+    }
+}
+
+impl Hit for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, alpha, beta) = self.intersect(r, t_min, t_max)?;
+
+        let mut rec = HitRecord::new(r, t, self.normal, &*self.mat);
+        (rec.u, rec.v) = (alpha, beta);
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // the box around both diagonals is the box around all four corners
+        let diagonal = |a: Point3, b: Point3| Aabb::new(a.min(b), a.max(b));
+        Some(
+            diagonal(self.q, self.q + self.u + self.v)
+                .surrounding(diagonal(self.q + self.u, self.q + self.v)),
+        )
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let r = Ray::new(origin, direction);
+        let t = match self.intersect(&r, 0.001, f64::INFINITY) {
+            Some((t, _, _)) => t,
+            None => return 0.0,
+        };
+
+        let distance_squared = t * t * direction.length_squared();
+        let cosine = direction.normalized().dot(self.normal).abs();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let mut rng = random::thread_rng();
+        let p = self.q + rng.gen::<f64>() * self.u + rng.gen::<f64>() * self.v;
+        p - origin
+    }
+}
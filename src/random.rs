@@ -0,0 +1,52 @@
+//! Provides the random number generator used throughout the
+//! crate. Like `rand::thread_rng`, every thread has its own
+//! generator, but it can be reseeded to make renders repeatable.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rand::rngs::StdRng;
+use rand::{Error, RngCore, SeedableRng};
+
+thread_local! {
+    static THREAD_RNG: Rc<RefCell<StdRng>> = Rc::new(RefCell::new(StdRng::from_entropy()));
+}
+
+/// A handle to the current thread's generator. It can't be sent to
+/// other threads, since it would keep using this thread's generator.
+#[derive(Clone, Debug)]
+pub struct ThreadRng {
+    rng: Rc<RefCell<StdRng>>,
+}
+
+/// Returns a handle to the current thread's generator,
+/// seeded from the operating system unless [`reseed`] was called.
+pub fn thread_rng() -> ThreadRng {
+    ThreadRng {
+        rng: THREAD_RNG.with(Rc::clone),
+    }
+}
+
+/// Restarts the current thread's generator from `seed`, so it gives
+/// the same numbers as any other generator started from it.
+pub fn reseed(seed: u64) {
+    THREAD_RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+impl RngCore for ThreadRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.borrow_mut().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.borrow_mut().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.borrow_mut().fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.borrow_mut().try_fill_bytes(dest)
+    }
+}
@@ -0,0 +1,217 @@
+//! Provides a renderer, which traces rays through every pixel
+//! of a scene's camera, and the framebuffer it renders into.
+
+use image::{ImageBuffer, RgbImage};
+use rand::Rng;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::ThreadPoolBuilder;
+
+use super::integrator::{Integrator, PathIntegrator};
+use super::random;
+use super::scene::Scene;
+use super::vec::Color;
+
+pub struct Renderer {
+    width: usize,
+    height: usize,
+    samples_per_pixel: u64,
+    max_depth: u64,
+    integrator: Box<dyn Integrator>,
+    /// The number of threads to render with, or none for one per core.
+    threads: Option<usize>,
+    /// Seeds the random numbers for every pixel, if the
+    /// render should come out the same every time.
+    seed: Option<u64>,
+    progress: Option<Box<dyn Fn(usize) + Send + Sync>>,
+}
+
+impl Renderer {
+    /// Creates a renderer for images of the given size, which uses a
+    /// [`PathIntegrator`] with 50 samples per pixel and at most 50 bounces.
+    ///
+    /// # Panics
+    ///
+    /// If the image would be empty, with no width or height.
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0, "the image can't be empty");
+
+        Self {
+            width,
+            height,
+            samples_per_pixel: 50,
+            max_depth: 50,
+            integrator: Box::new(PathIntegrator::new()),
+            threads: None,
+            seed: None,
+            progress: None,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If there are no samples, which would leave every pixel undefined.
+    pub fn with_samples_per_pixel(mut self, samples_per_pixel: u64) -> Self {
+        assert!(
+            samples_per_pixel > 0,
+            "each pixel needs at least one sample"
+        );
+        self.samples_per_pixel = samples_per_pixel;
+        self
+    }
+
+    /// Sets the most bounces a ray can take before it's given up on.
+    pub fn with_max_depth(mut self, max_depth: u64) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_integrator(mut self, integrator: Box<dyn Integrator>) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    /// Renders on its own pool of `threads` threads,
+    /// instead of on one thread per core.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Makes the render repeatable, by starting the random numbers for
+    /// every pixel from `seed` and the pixel's position. The same seed
+    /// gives the same image, no matter how many threads render it.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Calls `progress` with the number of rows left to render,
+    /// before starting on each one.
+    pub fn with_progress<F>(mut self, progress: F) -> Self
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Renders the scene from its camera.
+    ///
+    /// # Panics
+    /// If the thread pool can't be created.
+    pub fn render(&self, scene: &Scene) -> Framebuffer {
+        match self.threads {
+            Some(threads) => ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("failed to create the render threads")
+                .install(|| self.render_rows(scene)),
+            None => self.render_rows(scene),
+        }
+    }
+
+    fn render_rows(&self, scene: &Scene) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(self.width, self.height);
+
+        for j in (0..self.height).rev() {
+            if let Some(progress) = &self.progress {
+                progress(j + 1);
+            }
+
+            let row: Vec<Color> = (0..self.width)
+                .into_par_iter()
+                .map(|i| self.render_pixel(scene, i, j))
+                .collect();
+
+            // rows are counted from the bottom, but stored from the top
+            let y = self.height - j - 1;
+            framebuffer.pixels[y * self.width..(y + 1) * self.width].copy_from_slice(&row);
+        }
+
+        framebuffer
+    }
+
+    fn render_pixel(&self, scene: &Scene, i: usize, j: usize) -> Color {
+        if let Some(seed) = self.seed {
+            // spread consecutive pixels' seeds far apart
+            let pixel = (j * self.width + i) as u64;
+            random::reseed(seed ^ pixel.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        }
+
+        let mut rng = random::thread_rng();
+        let sum: Color = (0..self.samples_per_pixel)
+            .map(|_| {
+                let random_u: f64 = rng.gen();
+                let random_v: f64 = rng.gen();
+
+                let u = ((i as f64) + random_u) / (self.width as f64);
+                let v = ((j as f64) + random_v) / (self.height as f64);
+
+                let r = scene.camera().get_ray(u, v);
+                self.integrator.ray_color(&r, scene, self.max_depth)
+            })
+            .sum();
+
+        sum / self.samples_per_pixel as f64
+    }
+}
+
+/// A rendered image, holding the linear color of each pixel,
+/// averaged over all of its samples.
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    /// Row by row from the top.
+    pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+    /// Creates a black framebuffer.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::new(0.0, 0.0, 0.0); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The color of the pixel `x` columns from the left
+    /// and `y` rows from the top.
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// The pixels, row by row from the top.
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    /// Converts the framebuffer to an 8-bit image, gamma corrected
+    /// the same way as [`Color::to_rgb`].
+    pub fn to_image(&self) -> RgbImage {
+        ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+            self.pixel(x as usize, y as usize).to_rgb(1)
+        })
+    }
+}
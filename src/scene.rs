@@ -0,0 +1,61 @@
+//! Provides a scene, which bundles together everything
+//! needed to render an image: the objects in the world,
+//! the camera looking at them, the background behind
+//! them, and the lights worth sending rays straight toward.
+
+use super::background::{Background, Gradient};
+use super::camera::Camera;
+use super::hit::{Hit, World};
+
+pub struct Scene {
+    world: Box<dyn Hit>,
+    camera: Camera,
+    background: Box<dyn Background>,
+    lights: World,
+}
+
+impl Scene {
+    /// Creates a scene lit by the default sky gradient.
+    pub fn new(world: Box<dyn Hit>, camera: Camera) -> Self {
+        Self {
+            world,
+            camera,
+            background: Box::new(Gradient::sky()),
+            lights: Vec::new(),
+        }
+    }
+
+    pub fn with_background(mut self, background: Box<dyn Background>) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Sets the objects to sample light from at every diffuse hit.
+    ///
+    /// Light found by aiming at the lights and light found by the
+    /// material's scattered rays are combined with multiple importance
+    /// sampling weights, taken from the lights' `pdf_value`. Emitters
+    /// left out of the lights still count, just with more noise. Each
+    /// light's `random` has to pick directions with the density its
+    /// `pdf_value` gives, or the weights come out wrong.
+    pub fn with_lights(mut self, lights: World) -> Self {
+        self.lights = lights;
+        self
+    }
+
+    pub fn world(&self) -> &dyn Hit {
+        &*self.world
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn background(&self) -> &dyn Background {
+        &*self.background
+    }
+
+    pub fn lights(&self) -> &World {
+        &self.lights
+    }
+}
@@ -1,9 +1,15 @@
 //! Represents a sphere with a dynamic material.
 
+use std::f64::consts::PI;
 use std::sync::Arc;
 
+use rand::Rng;
+
+use super::aabb::Aabb;
 use super::hit::{Hit, HitRecord};
 use super::material::Scatter;
+use super::onb::Onb;
+use super::random;
 use super::ray::Ray;
 use super::vec::{Point3, Vec3};
 
@@ -21,10 +27,19 @@ impl Sphere {
             mat,
         }
     }
+
+    /// Maps a point on the unit sphere to texture coordinates, with
+    /// `u` going around the Y axis and `v` running from -Y to +Y.
+    fn uv(p: Point3) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
 }
 
 impl Hit for Sphere {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let oc = r.origin() - self.center;
         let a = r.direction().length().powi(2);
         let half_b = oc.dot(r.direction());
@@ -45,17 +60,55 @@ impl Hit for Sphere {
             }
         }
 
-        let mut rec = HitRecord {
-            t: root,
-            p: r.at(root),
-            mat: &*self.mat,
-            normal: Vec3::new(0.0, 0.0, 0.0),
-            front_face: false,
-        };
-
-        let outward_normal = (rec.p - self.center) / self.radius;
-        rec.set_face_normal(r, outward_normal);
+        let outward_normal = (r.at(root) - self.center) / self.radius;
+        let mut rec = HitRecord::new(r, root, outward_normal, &*self.mat);
+        (rec.u, rec.v) = Self::uv(outward_normal * self.radius.signum());
 
         Some(rec)
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // the radius may be negative for hollow spheres
+        let r = Vec3::new(self.radius.abs(), self.radius.abs(), self.radius.abs());
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self
+            .hit(&Ray::new(origin, direction), 0.001, f64::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            // from the inside, the sphere covers every direction
+            return 1.0 / (4.0 * PI);
+        }
+
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return Vec3::random_in_unit_sphere().normalized();
+        }
+
+        // pick uniformly from the cone of directions the sphere covers
+        let mut rng = random::thread_rng();
+        let (r1, r2): (f64, f64) = (rng.gen(), rng.gen());
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let z = 1.0 + r2 * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).sqrt();
+
+        Onb::new(direction).local(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z))
+    }
 }
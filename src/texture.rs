@@ -0,0 +1,73 @@
+//! Provides textures, which vary a material's
+//! properties across the surface of an object.
+
+use image::RgbImage;
+
+use super::vec::{Color, Point3};
+
+pub trait Texture: Send + Sync {
+    /// The texture's value at surface coordinates `u` and `v`,
+    /// where the surface point `p` is.
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color;
+}
+
+/// A texture that looks up its values in an image, repeating
+/// it over the surface. The image covers `u` and `v` from 0 to 1,
+/// with `v` going from the bottom of the image to the top.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// Linear values, row by row from the top.
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    /// Creates a texture from linear values, given row by row from the top.
+    ///
+    /// # Panics
+    /// If the number of pixels doesn't match the dimensions,
+    /// or the image is empty.
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        assert!(width > 0 && height > 0, "the image can't be empty");
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Creates a texture of colors from an image, undoing
+    /// the same gamma 2 that `Vec3::to_rgb` applies.
+    pub fn from_image(image: &RgbImage) -> Self {
+        Self::from_image_with(image, |v| (v as f64 / 255.0).powi(2))
+    }
+
+    /// Creates a texture from an image whose values aren't colors,
+    /// like roughness, which are stored without gamma correction.
+    pub fn from_linear_image(image: &RgbImage) -> Self {
+        Self::from_image_with(image, |v| v as f64 / 255.0)
+    }
+
+    fn from_image_with(image: &RgbImage, decode: impl Fn(u8) -> f64) -> Self {
+        let pixels = image
+            .pixels()
+            .map(|p| Color::new(decode(p[0]), decode(p[1]), decode(p[2])))
+            .collect();
+
+        Self::new(image.width() as usize, image.height() as usize, pixels)
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point3) -> Color {
+        // wrap around, and flip v to count rows from the top
+        let u = u.rem_euclid(1.0);
+        let v = 1.0 - v.rem_euclid(1.0);
+
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
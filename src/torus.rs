@@ -0,0 +1,151 @@
+//! Represents a torus with a dynamic material.
+//!
+//! A torus is the tube swept by a circle going around an axis,
+//! and meets a ray in up to four points, found by solving a quartic.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::disk;
+use super::hit::{self, Hit, HitRecord};
+use super::material::Scatter;
+use super::math;
+use super::onb::Onb;
+use super::random;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+pub struct Torus {
+    center: Point3,
+    /// Axes for the texture coordinates, with the torus's axis as `w`.
+    axes: Onb,
+    /// The distance from the center to the middle of the tube.
+    major_radius: f64,
+    /// The radius of the tube.
+    minor_radius: f64,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Torus {
+    /// Creates the torus around `center`, whose hole runs along `axis`,
+    /// or returns none if the axis is zero, or the minor radius isn't
+    /// between 0 and the major radius. A tube thicker than that would
+    /// make the torus pass through itself.
+    pub fn new(
+        center: Point3,
+        axis: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        mat: Arc<dyn Scatter>,
+    ) -> Option<Self> {
+        if axis.length() == 0.0 || !(0.0 < minor_radius && minor_radius < major_radius) {
+            return None;
+        }
+
+        Some(Self {
+            center,
+            axes: Onb::new(axis),
+            major_radius,
+            minor_radius,
+            mat,
+        })
+    }
+
+    fn area(&self) -> f64 {
+        4.0 * PI * PI * self.major_radius * self.minor_radius
+    }
+}
+
+impl Hit for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // with the center at the origin, the axis along +Z and lengths
+        // in units of the major radius, so the quartic's coefficients
+        // stay near 1 however big the torus is, and a unit direction
+        let scale = self.major_radius;
+        let minor = self.minor_radius / scale;
+        let o = self.axes.to_local(r.origin() - self.center) / scale;
+        let d = self.axes.to_local(r.direction());
+        let speed = d.length() / scale;
+        let d = d.normalized();
+
+        // skip to where the ray enters the bounding sphere, since
+        // the roots lose precision when the ray starts far away
+        let outer = 1.0 + minor;
+        let half_b = o.dot(d);
+        let discriminant = half_b * half_b - (o.length_squared() - outer * outer);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let skip = (-half_b - discriminant.sqrt()).max(0.0);
+        if skip / speed > t_max {
+            return None;
+        }
+        let o = o + skip * d;
+
+        let f = o.dot(d);
+        let e = o.length_squared() - 1.0 - minor * minor;
+        let roots = math::solve_quartic(
+            1.0,
+            4.0 * f,
+            2.0 * e + 4.0 * f * f + 4.0 * d.z() * d.z(),
+            4.0 * f * e + 8.0 * o.z() * d.z(),
+            e * e - 4.0 * (minor * minor - o.z() * o.z()),
+        );
+        let s = roots
+            .iter()
+            .copied()
+            .find(|&s| (t_min..=t_max).contains(&((skip + s) / speed)))?;
+        let t = (skip + s) / speed;
+
+        // the normal points away from the nearest point on the tube's middle
+        let p = o + s * d;
+        let ring_distance = p.x().hypot(p.y());
+        let ring = Vec3::new(p.x(), p.y(), 0.0) / ring_distance;
+        let outward_normal = self.axes.local((p - ring) / minor).normalized();
+
+        // `u` goes around the axis, and `v` around the tube,
+        // starting from its outside
+        let mut rec = HitRecord::new(r, t, outward_normal, &*self.mat);
+        rec.u = p.y().atan2(p.x()).rem_euclid(2.0 * PI) / (2.0 * PI);
+        rec.v = p.z().atan2(ring_distance - 1.0).rem_euclid(2.0 * PI) / (2.0 * PI);
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let ring = disk::circle_bounds(self.center, self.axes.w(), self.major_radius);
+        let tube = Vec3::new(self.minor_radius, self.minor_radius, self.minor_radius);
+        Some(Aabb::new(ring.min() - tube, ring.max() + tube))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        hit::area_pdf_value(self, self.area(), origin, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let mut rng = random::thread_rng();
+        let (major, minor) = (self.major_radius, self.minor_radius);
+
+        // the outside of the tube has more area than the inside, so
+        // angles around it are kept in proportion to the distance
+        // from the axis, by rejecting the rest
+        let theta = loop {
+            let theta = 2.0 * PI * rng.gen::<f64>();
+            if rng.gen::<f64>() * (major + minor) <= major + minor * theta.cos() {
+                break theta;
+            }
+        };
+        let phi = 2.0 * PI * rng.gen::<f64>();
+
+        let distance = major + minor * theta.cos();
+        let p = Vec3::new(
+            distance * phi.cos(),
+            distance * phi.sin(),
+            minor * theta.sin(),
+        );
+        self.center + self.axes.local(p) - origin
+    }
+}
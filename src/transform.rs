@@ -0,0 +1,284 @@
+//! Provides transformations, which move, turn and scale points
+//! and vectors. A transformation is kept together with its inverse,
+//! so rays can be taken into an object's own space as cheaply as
+//! the hits can be brought back out of it.
+
+use std::ops::Mul;
+
+use super::aabb::Aabb;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+/// An affine transformation, as a 4x4 matrix that
+/// multiplies column vectors from the left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix {
+    m: [[f64; 4]; 4],
+}
+
+impl Matrix {
+    pub const IDENTITY: Self = Self {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    /// Creates a matrix from its rows.
+    pub fn new(rows: [[f64; 4]; 4]) -> Self {
+        Self { m: rows }
+    }
+
+    /// Creates a matrix from its columns, the order
+    /// most file formats list the numbers in.
+    pub fn from_columns(columns: [[f64; 4]; 4]) -> Self {
+        Self { m: columns }.transpose()
+    }
+
+    pub fn rows(&self) -> [[f64; 4]; 4] {
+        self.m
+    }
+
+    pub fn translate(delta: Vec3) -> Self {
+        let mut t = Self::IDENTITY;
+        for i in 0..3 {
+            t.m[i][3] = delta[i];
+        }
+        t
+    }
+
+    pub fn scale(x: f64, y: f64, z: f64) -> Self {
+        let mut t = Self::IDENTITY;
+        t.m[0][0] = x;
+        t.m[1][1] = y;
+        t.m[2][2] = z;
+        t
+    }
+
+    /// A rotation counter-clockwise around `axis`, by `angle` degrees.
+    pub fn rotate(angle: f64, axis: Vec3) -> Self {
+        let a = axis.normalized();
+        let (sin, cos) = angle.to_radians().sin_cos();
+
+        let mut t = Self::IDENTITY;
+        for i in 0..3 {
+            for j in 0..3 {
+                t.m[i][j] = a[i] * a[j] * (1.0 - cos)
+                    + match (i, j) {
+                        _ if i == j => cos,
+                        // the cross product terms, signed by the order of the axes
+                        (0, 1) | (1, 2) | (2, 0) => -a[3 - i - j] * sin,
+                        _ => a[3 - i - j] * sin,
+                    };
+            }
+        }
+        t
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut t = Self::IDENTITY;
+        for i in 0..4 {
+            for j in 0..4 {
+                t.m[i][j] = self.m[j][i];
+            }
+        }
+        t
+    }
+
+    /// Inverts the matrix by Gauss-Jordan elimination,
+    /// or returns none if it can't be inverted.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::IDENTITY.m;
+
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in (0..4).filter(|&row| row != col) {
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+
+        Some(Self { m: inv })
+    }
+
+    /// The determinant of the linear part, which is negative
+    /// if the transformation mirrors what it's applied to.
+    pub fn determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.vector(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let row = |i: usize| self.m[i][0] * v[0] + self.m[i][1] * v[1] + self.m[i][2] * v[2];
+        Vec3::new(row(0), row(1), row(2))
+    }
+}
+
+impl Mul for Matrix {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let mut t = Self::IDENTITY;
+        for i in 0..4 {
+            for j in 0..4 {
+                t.m[i][j] = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        t
+    }
+}
+
+/// A transformation that can be undone. Like matrices, transformations
+/// are combined by multiplying them, so `a * b` applies `b` first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        matrix: Matrix::IDENTITY,
+        inverse: Matrix::IDENTITY,
+    };
+
+    /// Creates a transformation from its matrix, or
+    /// returns none if the matrix can't be inverted.
+    pub fn new(matrix: Matrix) -> Option<Self> {
+        Some(Self {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn translate(delta: Vec3) -> Self {
+        Self {
+            matrix: Matrix::translate(delta),
+            inverse: Matrix::translate(-delta),
+        }
+    }
+
+    /// # Panics
+    /// If any of the factors is zero, which would flatten
+    /// everything in a way that can't be undone.
+    pub fn scale(x: f64, y: f64, z: f64) -> Self {
+        assert!(
+            x != 0.0 && y != 0.0 && z != 0.0,
+            "can't scale by zero, since it can't be undone"
+        );
+
+        Self {
+            matrix: Matrix::scale(x, y, z),
+            inverse: Matrix::scale(1.0 / x, 1.0 / y, 1.0 / z),
+        }
+    }
+
+    /// A rotation counter-clockwise around `axis`, by `angle` degrees.
+    pub fn rotate(angle: f64, axis: Vec3) -> Self {
+        let matrix = Matrix::rotate(angle, axis);
+        Self {
+            matrix,
+            // rotations are undone by their transpose
+            inverse: matrix.transpose(),
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix {
+        self.matrix
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    /// The matrix that transforms normals, so that they stay
+    /// perpendicular to the surfaces they belong to.
+    pub fn normal_matrix(&self) -> Matrix {
+        self.inverse.transpose()
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.matrix.point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.vector(v)
+    }
+
+    /// Transforms a normal, which might not be of unit length afterwards.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        // the inverse's transpose, without transposing
+        let column = |j: usize| {
+            self.inverse.m[0][j] * n[0] + self.inverse.m[1][j] * n[1] + self.inverse.m[2][j] * n[2]
+        };
+        Vec3::new(column(0), column(1), column(2))
+    }
+
+    /// Transforms a ray, keeping its direction unnormalized, so
+    /// that it reaches the same points at the same times `t`.
+    pub fn ray(&self, r: &Ray) -> Ray {
+        Ray::new(self.point(r.origin()), self.vector(r.direction()))
+    }
+
+    /// A box around the transformed box, which encloses anything
+    /// the original box did, after it's been transformed.
+    pub fn bounding_box(&self, b: Aabb) -> Aabb {
+        let extent = b.extent();
+        if extent.x() < 0.0 || extent.y() < 0.0 || extent.z() < 0.0 {
+            // the empty box
+            return b;
+        }
+
+        (0..8)
+            .map(|corner| {
+                let pick = |axis: usize| {
+                    if corner & (1 << axis) == 0 {
+                        b.min()[axis]
+                    } else {
+                        b.max()[axis]
+                    }
+                };
+                let p = self.point(Point3::new(pick(0), pick(1), pick(2)));
+                Aabb::new(p, p)
+            })
+            .fold(Aabb::empty(), Aabb::surrounding)
+    }
+}
+
+impl Mul for Transform {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            matrix: self.matrix * rhs.matrix,
+            inverse: rhs.inverse * self.inverse,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Represents a triangle with a dynamic material.
+//!
+//! Besides its three corners, a triangle can carry a normal
+//! and texture coordinates for each vertex, which get
+//! interpolated across its face.
+
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::random;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+pub struct Triangle {
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    mat: Arc<dyn Scatter>,
+}
+
+impl Triangle {
+    /// Creates a triangle whose front face is the one the
+    /// vertices wind counter-clockwise around.
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, mat: Arc<dyn Scatter>) -> Self {
+        Self {
+            vertices: [v0, v1, v2],
+            normals: None,
+            uvs: None,
+            mat,
+        }
+    }
+
+    /// Sets per-vertex normals, which get interpolated into a smooth shading normal.
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    /// Sets per-vertex texture coordinates.
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+impl Hit for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, b1, b2) = intersect(r, self.vertices, t_min, t_max)?;
+
+        Some(hit_record(
+            r,
+            t,
+            [1.0 - b1 - b2, b1, b2],
+            self.vertices,
+            self.normals,
+            self.uvs,
+            &*self.mat,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(bounding_box(self.vertices))
+    }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let r = Ray::new(origin, direction);
+        match intersect(&r, self.vertices, 0.001, f64::INFINITY) {
+            Some((t, _, _)) => solid_angle_factor(&r, t, self.vertices) / area(self.vertices),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        random_point(self.vertices) - origin
+    }
+}
+
+/// Intersects a ray with the triangle `v` using the Möller–Trumbore
+/// algorithm, returning the distance along the ray and the
+/// barycentric weights of the second and third vertices.
+pub(crate) fn intersect(
+    r: &Ray,
+    v: [Point3; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    const EPS: f64 = 1.0e-12;
+
+    let edge1 = v[1] - v[0];
+    let edge2 = v[2] - v[0];
+    let pvec = r.direction().cross(edge2);
+    let det = edge1.dot(pvec);
+    if det.abs() < EPS {
+        // the ray is parallel to the triangle
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let tvec = r.origin() - v[0];
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let b2 = r.direction().dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(qvec) * inv_det;
+    if t < t_min || t > t_max {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+/// Fills in a hit record for a hit on the triangle `v`,
+/// interpolating whatever per-vertex data is available.
+pub(crate) fn hit_record<'a>(
+    r: &Ray,
+    t: f64,
+    barycentric: [f64; 3],
+    v: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    mat: &'a dyn Scatter,
+) -> HitRecord<'a> {
+    let [b0, b1, b2] = barycentric;
+
+    let mut outward_normal = (v[1] - v[0]).cross(v[2] - v[0]).normalized();
+    let shading_normal = normals.map(|n| (b0 * n[0] + b1 * n[1] + b2 * n[2]).normalized());
+    if let Some(n) = shading_normal {
+        // trust the vertex normals over the winding order
+        // to tell which side is the outside
+        if n.dot(outward_normal) < 0.0 {
+            outward_normal = -outward_normal;
+        }
+    }
+
+    let mut rec = HitRecord::new(r, t, outward_normal, mat);
+    if let Some(n) = shading_normal {
+        rec.set_shading_normal(n);
+    }
+
+    (rec.u, rec.v) = match uvs {
+        Some(uv) => (
+            b0 * uv[0].0 + b1 * uv[1].0 + b2 * uv[2].0,
+            b0 * uv[0].1 + b1 * uv[1].1 + b2 * uv[2].1,
+        ),
+        None => (b1, b2),
+    };
+    rec.barycentric = Some(barycentric);
+
+    rec
+}
+
+pub(crate) fn area(v: [Point3; 3]) -> f64 {
+    0.5 * (v[1] - v[0]).cross(v[2] - v[0]).length()
+}
+
+/// Picks a point on the triangle `v`, uniformly by area.
+pub(crate) fn random_point(v: [Point3; 3]) -> Point3 {
+    let mut rng = random::thread_rng();
+    let su = rng.gen::<f64>().sqrt();
+    let b0 = 1.0 - su;
+    let b1 = rng.gen::<f64>() * su;
+
+    b0 * v[0] + b1 * v[1] + (1.0 - b0 - b1) * v[2]
+}
+
+/// Converts a density over the area of the triangle `v` to one over
+/// solid angle, when seen along `r`, which hits it at distance `t`.
+pub(crate) fn solid_angle_factor(r: &Ray, t: f64, v: [Point3; 3]) -> f64 {
+    let normal = (v[1] - v[0]).cross(v[2] - v[0]).normalized();
+    let distance_squared = t * t * r.direction().length_squared();
+    let cosine = r.direction().normalized().dot(normal).abs();
+
+    distance_squared / cosine
+}
+
+pub(crate) fn bounding_box(v: [Point3; 3]) -> Aabb {
+    Aabb::new(v[0].min(v[1]).min(v[2]), v[0].max(v[1]).max(v[2]))
+}
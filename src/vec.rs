@@ -12,8 +12,14 @@ use std::{
 
 use image::Rgb;
 use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug)]
+use super::random;
+
+/// Serializes as an array of its three components.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
 pub struct Vec3 {
     e: [f64; 3],
 }
@@ -27,7 +33,7 @@ impl Vec3 {
     }
 
     pub fn random(r: Range<f64>) -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::thread_rng();
 
         Self {
             e: [
@@ -57,7 +63,7 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_disk() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::thread_rng();
 
         loop {
             let p = Self::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
@@ -115,6 +121,22 @@ impl Vec3 {
         )
     }
 
+    pub fn min(self, rhs: Self) -> Self {
+        Self::new(
+            self[0].min(rhs[0]),
+            self[1].min(rhs[1]),
+            self[2].min(rhs[2]),
+        )
+    }
+
+    pub fn max(self, rhs: Self) -> Self {
+        Self::new(
+            self[0].max(rhs[0]),
+            self[1].max(rhs[1]),
+            self[2].max(rhs[2]),
+        )
+    }
+
     pub fn normalized(self) -> Self {
         self.div(self.length())
     }